@@ -2,7 +2,7 @@ use copp_rs::ijvm_core::init_ijvm;
 use criterion::{criterion_group, criterion_main, Criterion};
 
 pub fn mandelbread_benchmark(c: &mut Criterion) {
-    let mut runtime = init_ijvm("files/advanced/mandelbread.ijvm");
+    let mut runtime = init_ijvm("files/advanced/mandelbread.ijvm").with_output(std::io::sink());
 
     // #[cfg(not(feature = "unsafe"))]
     // {
@@ -31,7 +31,8 @@ pub fn mandelbread_bench_full(c: &mut Criterion) {
 
     g.bench_function("mandelbread-full", |b| {
         b.iter(|| {
-            let mut runtime = init_ijvm("files/advanced/mandelbread.ijvm");
+            let mut runtime =
+                init_ijvm("files/advanced/mandelbread.ijvm").with_output(std::io::sink());
             runtime.run();
         })
     });
@@ -0,0 +1,14 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    {
+        // into OUT_DIR, tests/capi.rs checks the committed include/ijvm.h against it
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let header = format!("{}/ijvm.h", std::env::var("OUT_DIR").unwrap());
+        cbindgen::generate(&crate_dir)
+            .expect("Unable to generate the C header")
+            .write_to_file(&header);
+        println!("cargo:rustc-env=IJVM_H={}", header);
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+    }
+}
@@ -0,0 +1,4 @@
+//! The C interface of [`copp_rs::capi`], built as a shared library. It's a crate of
+//! its own because a cdylib can't be built without std.
+
+pub use copp_rs::capi::*;
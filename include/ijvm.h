@@ -0,0 +1,77 @@
+#ifndef IJVM_H
+#define IJVM_H
+
+/* Generated from src/capi.rs by build.rs, don't edit. */
+
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+
+typedef uint8_t byte_t;
+
+// A value on the stack, in a local or in the constant pool.
+typedef int32_t word_t;
+
+// Loads the binary at `binary_path`, replacing the loaded one. `IN` reads from
+// stdin and `OUT` writes to stdout until `set_input` and `set_output` say
+// otherwise. Returns 0 on success, -1 if the binary can't be loaded.
+//
+// # Safety
+//
+// `binary_path` is a NUL-terminated string.
+int init_ijvm(const char *binary_path);
+
+// Unloads the binary, flushing its output.
+void destroy_ijvm(void);
+
+// Makes `IN` read from `fp`, or stdin again if it's NULL.
+//
+// # Safety
+//
+// `fp` stays open until the binary is unloaded or the input is set again.
+void set_input(FILE *fp);
+
+// Makes `OUT` write to `fp`, or stdout again if it's NULL.
+//
+// # Safety
+//
+// `fp` stays open until the binary is unloaded or the output is set again.
+void set_output(FILE *fp);
+
+// Runs until the program halts or fails.
+void run(void);
+
+// Executes one instruction, a WIDE one included. Returns false if the program
+// had already finished, or the instruction failed.
+bool step(void);
+
+// Whether the program halted or failed, true if nothing is loaded.
+bool finished(void);
+
+// The byte offset of the next instruction in the text.
+int get_program_counter(void);
+
+// The byte of the text at the program counter, the opcode of the next
+// instruction. 0 past the end of the text.
+byte_t get_instruction(void);
+
+// The text of the binary, valid until it is unloaded.
+const byte_t *get_text(void);
+
+int get_text_size(void);
+
+// The value on top of the stack.
+word_t tos(void);
+
+// The stack, bottom first, valid until the machine runs again.
+const word_t *get_stack(void);
+
+int stack_size(void);
+
+// Local variable `i` of the current frame, 0 if it doesn't have one.
+word_t get_local_variable(int i);
+
+// Constant `i` of the constant pool, 0 if there isn't one.
+word_t get_constant(int i);
+
+#endif  /* IJVM_H */
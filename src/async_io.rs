@@ -0,0 +1,155 @@
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    ijvm_core::{InputMode, Outcome, Runtime},
+    io::{self, Read},
+    observer::Observer,
+    trap::TrapKind,
+};
+
+/// Fuel spent before yielding to the executor, with the default cost table that
+/// many instructions.
+const BURST: u64 = 1 << 16;
+
+/// Where `IN` reads from in [`Runtime::run_async`].
+pub trait AsyncInput {
+    /// Reads into `buf` and returns how many bytes were read, 0 at the end of input.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+}
+
+/// Where `OUT` writes to in [`Runtime::run_async`].
+pub trait AsyncOutput {
+    fn write_all(&mut self, buf: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+impl AsyncInput for &[u8] {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(self, buf)
+    }
+}
+
+impl AsyncOutput for Vec<u8> {
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl<R: Read, O: Observer> Runtime<R, Vec<u8>, O> {
+    /// Runs like [`Runtime::run`], but `IN` and `OUT` go through async streams and
+    /// never block the thread. Works with any executor. The runtime's own output
+    /// has to be a `Vec<u8>`, see [`Runtime::with_output`], where `OUT` bytes wait
+    /// to be written.
+    ///
+    /// The program runs synchronously in bursts. Execution pauses when `IN` runs
+    /// out of input, then more is awaited, and after `OUT`, then the byte is
+    /// written. After a fixed amount of fuel the task yields to the executor. The
+    /// fuel counts towards [`Runtime::fuel_used`]. The input mode is back as it
+    /// was afterwards, also when the future is dropped before it's done.
+    pub async fn run_async(
+        &mut self,
+        input: &mut impl AsyncInput,
+        output: &mut impl AsyncOutput,
+    ) -> Outcome {
+        let mode = self.input_mode();
+        self.set_input_mode(InputMode::NonBlocking);
+        let mut runtime = RestoreInputMode {
+            runtime: self,
+            mode,
+        };
+
+        let mut fuel = BURST;
+        loop {
+            let fuel_used = runtime.fuel_used();
+            let mut outcome =
+                runtime.run_with_fuel_until(fuel, |runtime| !runtime.inner.out_stream.is_empty());
+            let spent = runtime.fuel_used() - fuel_used;
+            if outcome == Outcome::BudgetExhausted && spent == 0 && fuel == BURST {
+                // the next instruction costs more than a burst, it gets one to itself
+                outcome = runtime.step();
+                fuel = 0;
+            } else {
+                fuel -= spent;
+            }
+
+            if !runtime.inner.out_stream.is_empty() {
+                let written = output.write_all(&runtime.inner.out_stream).await;
+                runtime.inner.out_stream.clear();
+                if let Err(e) = written {
+                    runtime.inner.trap(TrapKind::Io(e.kind()));
+                    return runtime.outcome();
+                }
+            }
+
+            match outcome {
+                Outcome::NeedsInput => {
+                    let mut buf = [0u8; 256];
+                    match input.read(&mut buf).await {
+                        Ok(0) => runtime.close_input(),
+                        Ok(n) => runtime.feed_input(&buf[..n]),
+                        Err(e) => {
+                            runtime.inner.trap(TrapKind::Io(e.kind()));
+                            return runtime.outcome();
+                        }
+                    }
+                }
+                // paused after OUT
+                Outcome::Running if fuel > 0 => {}
+                Outcome::Running | Outcome::BudgetExhausted => {
+                    YieldNow(false).await;
+                    fuel = BURST;
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+}
+
+/// Gives the runtime back its input mode when [`Runtime::run_async`] ends, or
+/// its future is dropped.
+struct RestoreInputMode<'a, R: Read, O: Observer> {
+    runtime: &'a mut Runtime<R, Vec<u8>, O>,
+    mode: InputMode,
+}
+
+impl<R: Read, O: Observer> Deref for RestoreInputMode<'_, R, O> {
+    type Target = Runtime<R, Vec<u8>, O>;
+
+    fn deref(&self) -> &Self::Target {
+        self.runtime
+    }
+}
+
+impl<R: Read, O: Observer> DerefMut for RestoreInputMode<'_, R, O> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.runtime
+    }
+}
+
+impl<R: Read, O: Observer> Drop for RestoreInputMode<'_, R, O> {
+    fn drop(&mut self) {
+        self.runtime.set_input_mode(self.mode);
+    }
+}
+
+/// Returns to the executor once, asking to be polled again right away.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::ijvm_core::{Outcome, Program};
+
+/// How one run of a [`Batch`] went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    /// Everything the program wrote with `OUT`.
+    pub output: Vec<u8>,
+    pub outcome: Outcome,
+    /// Number of instructions executed.
+    pub instructions: u64,
+    /// Time spent executing, not counting setting up the runtime.
+    pub time: Duration,
+}
+
+/// Runs one program against many inputs on a pool of threads.
+pub struct Batch<'a> {
+    program: &'a Program,
+    threads: usize,
+    instruction_limit: u64,
+}
+
+impl<'a> Batch<'a> {
+    /// A batch using every available core and no instruction limit.
+    pub fn new(program: &'a Program) -> Batch<'a> {
+        Batch {
+            program,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            instruction_limit: u64::MAX,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Batch<'a> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Stops a run with [`Outcome::BudgetExhausted`] after `limit` instructions,
+    /// so a program stuck in a loop doesn't hold up the batch.
+    pub fn with_instruction_limit(mut self, limit: u64) -> Batch<'a> {
+        self.instruction_limit = limit;
+        self
+    }
+
+    /// Runs the program once per input, which `IN` reads from. The results are in
+    /// the same order as `inputs`.
+    pub fn run<I: AsRef<[u8]> + Sync>(&self, inputs: &[I]) -> Vec<BatchResult> {
+        let next = AtomicUsize::new(0);
+        let worker = || {
+            let mut results = Vec::new();
+            loop {
+                let ind = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(ind) else {
+                    return results;
+                };
+                results.push((ind, self.run_one(input.as_ref())));
+            }
+        };
+
+        let mut results: Vec<Option<BatchResult>> = vec![None; inputs.len()];
+        thread::scope(|scope| {
+            let workers = (0..self.threads.min(inputs.len()))
+                .map(|_| scope.spawn(worker))
+                .collect::<Vec<_>>();
+            for handle in workers {
+                for (ind, result) in handle.join().unwrap() {
+                    results[ind] = Some(result);
+                }
+            }
+        });
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    fn run_one(&self, input: &[u8]) -> BatchResult {
+        let mut runtime = self
+            .program
+            .runtime()
+            .with_input(input)
+            .with_output(Vec::new());
+
+        let start = Instant::now();
+        let outcome = runtime.run_with_fuel(self.instruction_limit);
+        let time = start.elapsed();
+
+        BatchResult {
+            instructions: runtime.fuel_used(),
+            output: std::mem::take(&mut runtime.inner.out_stream),
+            outcome,
+            time,
+        }
+    }
+}
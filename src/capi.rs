@@ -0,0 +1,200 @@
+#![allow(non_camel_case_types)]
+
+use std::{
+    ffi::{c_char, c_int, CStr},
+    io::{self, Read, Write},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::compat::Ijvm;
+
+/// A value on the stack, in a local or in the constant pool.
+pub type word_t = i32;
+pub type byte_t = u8;
+
+// the one machine of the C interface, which has no handles
+static LOADED: Mutex<Option<Ijvm>> = Mutex::new(None);
+
+/// A stream opened by the C side, which keeps it open while the machine uses it.
+struct CFile(*mut libc::FILE);
+
+// the machine only uses it behind the lock
+unsafe impl Send for CFile {}
+
+impl Read for CFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match unsafe { libc::fgetc(self.0) } {
+            libc::EOF => Ok(0),
+            byte => {
+                buf[0] = byte as u8;
+                Ok(1)
+            }
+        }
+    }
+}
+
+impl Write for CFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = unsafe { libc::fwrite(buf.as_ptr().cast(), 1, buf.len(), self.0) };
+        if written == 0 && !buf.is_empty() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match unsafe { libc::fflush(self.0) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+fn with_loaded<T>(default: T, f: impl FnOnce(&mut Ijvm) -> T) -> T {
+    let mut loaded = LOADED.lock().unwrap_or_else(PoisonError::into_inner);
+    match loaded.as_mut() {
+        Some(ijvm) => f(ijvm),
+        None => default,
+    }
+}
+
+/// Loads the binary at `binary_path`, replacing the loaded one. `IN` reads from
+/// stdin and `OUT` writes to stdout until `set_input` and `set_output` say
+/// otherwise. Returns 0 on success, -1 if the binary can't be loaded.
+///
+/// # Safety
+///
+/// `binary_path` is a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn init_ijvm(binary_path: *const c_char) -> c_int {
+    destroy_ijvm();
+    if binary_path.is_null() {
+        return -1;
+    }
+    let Ok(path) = CStr::from_ptr(binary_path).to_str() else {
+        return -1;
+    };
+    let Ok(ijvm) = Ijvm::init(path) else {
+        return -1;
+    };
+    *LOADED.lock().unwrap_or_else(PoisonError::into_inner) = Some(ijvm);
+    0
+}
+
+/// Unloads the binary, flushing its output.
+#[no_mangle]
+pub extern "C" fn destroy_ijvm() {
+    let mut loaded = LOADED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(mut ijvm) = loaded.take() {
+        let _ = ijvm.flush_output();
+    }
+}
+
+/// Makes `IN` read from `fp`, or stdin again if it's NULL.
+///
+/// # Safety
+///
+/// `fp` stays open until the binary is unloaded or the input is set again.
+#[no_mangle]
+pub unsafe extern "C" fn set_input(fp: *mut libc::FILE) {
+    let input: Box<dyn Read + Send> = match fp.is_null() {
+        true => Box::new(io::stdin()),
+        false => Box::new(CFile(fp)),
+    };
+    with_loaded((), |ijvm| ijvm.set_input_stream(input))
+}
+
+/// Makes `OUT` write to `fp`, or stdout again if it's NULL.
+///
+/// # Safety
+///
+/// `fp` stays open until the binary is unloaded or the output is set again.
+#[no_mangle]
+pub unsafe extern "C" fn set_output(fp: *mut libc::FILE) {
+    let output: Box<dyn Write + Send> = match fp.is_null() {
+        true => Box::new(io::stdout()),
+        false => Box::new(CFile(fp)),
+    };
+    with_loaded((), |ijvm| {
+        let _ = ijvm.set_output_stream(output);
+    })
+}
+
+/// Runs until the program halts or fails.
+#[no_mangle]
+pub extern "C" fn run() {
+    with_loaded((), Ijvm::run)
+}
+
+/// Executes one instruction, a WIDE one included. Returns false if the program
+/// had already finished, or the instruction failed.
+#[no_mangle]
+pub extern "C" fn step() -> bool {
+    with_loaded(false, Ijvm::step)
+}
+
+/// Whether the program halted or failed, true if nothing is loaded.
+#[no_mangle]
+pub extern "C" fn finished() -> bool {
+    with_loaded(true, |ijvm| ijvm.finished())
+}
+
+/// The byte offset of the next instruction in the text.
+#[no_mangle]
+pub extern "C" fn get_program_counter() -> c_int {
+    with_loaded(0, |ijvm| ijvm.get_program_counter() as c_int)
+}
+
+/// The byte of the text at the program counter, the opcode of the next
+/// instruction. 0 past the end of the text.
+#[no_mangle]
+pub extern "C" fn get_instruction() -> byte_t {
+    with_loaded(0, |ijvm| ijvm.get_instruction())
+}
+
+/// The text of the binary, valid until it is unloaded.
+#[no_mangle]
+pub extern "C" fn get_text() -> *const byte_t {
+    with_loaded(std::ptr::null(), |ijvm| ijvm.get_text().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn get_text_size() -> c_int {
+    with_loaded(0, |ijvm| ijvm.get_text_size() as c_int)
+}
+
+/// The value on top of the stack.
+#[no_mangle]
+pub extern "C" fn tos() -> word_t {
+    with_loaded(0, |ijvm| ijvm.tos())
+}
+
+/// The stack, bottom first, valid until the machine runs again.
+#[no_mangle]
+pub extern "C" fn get_stack() -> *const word_t {
+    with_loaded(std::ptr::null(), |ijvm| ijvm.get_stack().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn stack_size() -> c_int {
+    with_loaded(0, |ijvm| ijvm.stack_size() as c_int)
+}
+
+/// Local variable `i` of the current frame, 0 if it doesn't have one.
+#[no_mangle]
+pub extern "C" fn get_local_variable(i: c_int) -> word_t {
+    with_loaded(0, |ijvm| {
+        usize::try_from(i).map_or(0, |i| ijvm.get_local_variable(i))
+    })
+}
+
+/// Constant `i` of the constant pool, 0 if there isn't one.
+#[no_mangle]
+pub extern "C" fn get_constant(i: c_int) -> word_t {
+    with_loaded(0, |ijvm| {
+        usize::try_from(i).map_or(0, |i| ijvm.get_constant(i))
+    })
+}
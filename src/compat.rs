@@ -0,0 +1,128 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+};
+
+use crate::ijvm_core::{Outcome, Program, Runtime};
+
+/// A machine answering what the course's reference tests ask, the way its
+/// `ijvm.h` answers: the program counter is a byte offset into the text, locals
+/// are the current frame's, and input and output are files. `OUT` writes to
+/// stdout until [`Ijvm::set_output`] says otherwise. The C library is built on it.
+pub struct Ijvm {
+    program: Program,
+    runtime: Runtime<Box<dyn Read + Send>, Box<dyn Write + Send>>,
+}
+
+impl Ijvm {
+    /// Loads the binary at `binary_path`, failing where `init_ijvm` returns -1.
+    pub fn init(binary_path: &str) -> io::Result<Ijvm> {
+        let program = Program::try_load(binary_path)?;
+        let input: Box<dyn Read + Send> = Box::new(io::stdin());
+        let output: Box<dyn Write + Send> = Box::new(io::stdout());
+        let runtime = program.runtime().with_input(input).with_output(output);
+        Ok(Ijvm { program, runtime })
+    }
+
+    /// Makes `IN` read from the file at `path`.
+    pub fn set_input(&mut self, path: &str) -> io::Result<()> {
+        self.set_input_stream(Box::new(BufReader::new(File::open(path)?)));
+        Ok(())
+    }
+
+    /// Makes `OUT` write to the file at `path`, which is created or truncated.
+    pub fn set_output(&mut self, path: &str) -> io::Result<()> {
+        self.set_output_stream(Box::new(File::create(path)?))
+    }
+
+    pub fn set_input_stream(&mut self, input: Box<dyn Read + Send>) {
+        *self.runtime.inner.in_stream() = input;
+    }
+
+    /// Makes `OUT` write to `output`, after flushing the previous output. That
+    /// output's flush error is returned, the new one is used either way.
+    pub fn set_output_stream(&mut self, output: Box<dyn Write + Send>) -> io::Result<()> {
+        let flushed = self.flush_output();
+        self.runtime.inner.out_stream = output;
+        flushed
+    }
+
+    pub fn flush_output(&mut self) -> io::Result<()> {
+        self.runtime.inner.out_stream.flush()
+    }
+
+    /// Executes one instruction, a WIDE one included. Returns false if the program
+    /// had already finished, or the instruction failed.
+    pub fn step(&mut self) -> bool {
+        if self.runtime.is_finished() {
+            return false;
+        }
+        let outcome = self.runtime.step();
+        let _ = self.flush_output();
+        matches!(outcome, Outcome::Running | Outcome::Halted)
+    }
+
+    /// Runs until the program halts or fails.
+    pub fn run(&mut self) {
+        self.runtime.run();
+        let _ = self.flush_output();
+    }
+
+    pub fn finished(&self) -> bool {
+        self.runtime.is_finished()
+    }
+
+    pub fn tos(&self) -> i32 {
+        self.runtime.tos()
+    }
+
+    /// The stack, bottom first.
+    pub fn get_stack(&self) -> &[i32] {
+        self.runtime.stack_slice()
+    }
+
+    pub fn stack_size(&self) -> usize {
+        self.runtime.stack_slice().len()
+    }
+
+    /// The byte offset of the next instruction in the text.
+    pub fn get_program_counter(&self) -> usize {
+        self.runtime.program_offset()
+    }
+
+    /// The byte of the text the program counter is at, which is the opcode of the
+    /// next instruction, whatever breakpoints are set. 0 past the end of the text.
+    pub fn get_instruction(&self) -> u8 {
+        self.get_text()
+            .get(self.get_program_counter())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn get_text(&self) -> &[u8] {
+        self.program.text()
+    }
+
+    pub fn get_text_size(&self) -> usize {
+        self.program.text().len()
+    }
+
+    /// Local variable `i` of the current frame, 0 if it doesn't have one.
+    pub fn get_local_variable(&self, i: usize) -> i32 {
+        self.runtime
+            .call_stack()
+            .next()
+            .and_then(|frame| frame.locals.get(i).copied())
+            .unwrap_or(0)
+    }
+
+    /// Constant `i` of the constant pool, 0 if there isn't one.
+    pub fn get_constant(&self, i: usize) -> i32 {
+        self.program.constants().get(i).copied().unwrap_or(0)
+    }
+
+    /// The machine underneath, for everything the reference interface doesn't have.
+    pub fn runtime(&mut self) -> &mut Runtime<Box<dyn Read + Send>, Box<dyn Write + Send>> {
+        &mut self.runtime
+    }
+}
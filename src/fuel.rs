@@ -0,0 +1,36 @@
+use crate::instructions::MemoryBlock;
+
+/// Fuel charged per instruction by [`crate::ijvm_core::Runtime::run_with_fuel`],
+/// indexed by opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostTable {
+    costs: [u32; 256],
+}
+
+impl CostTable {
+    /// Every instruction costs 1, so fuel counts instructions.
+    pub fn uniform() -> CostTable {
+        CostTable { costs: [1; 256] }
+    }
+
+    /// Charges `cost` for the instruction with `opcode`, e.g. `0xB6` for INVOKEVIRTUAL.
+    pub fn with_cost(mut self, opcode: u8, cost: u32) -> CostTable {
+        self.set_cost(opcode, cost);
+        self
+    }
+
+    pub fn set_cost(&mut self, opcode: u8, cost: u32) {
+        self.costs[opcode as usize] = cost;
+    }
+
+    #[inline]
+    pub fn cost(&self, instruction: &MemoryBlock) -> u64 {
+        self.costs[instruction.opcode() as usize] as u64
+    }
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        Self::uniform()
+    }
+}
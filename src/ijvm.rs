@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use crate::{ijvm_core::InstructionRef, tiny::TinyVars};
 
 pub struct IJVMBlock {
@@ -46,17 +48,64 @@ pub struct Frame {
     starting_stack_length: u32,
     vars: TinyVars,
     restore_pc: InstructionRef,
+    // header of the frame's method, 0 for main; fits next to starting_stack_length
+    method: u32,
+}
+
+/// A frame as seen from outside the runtime, see [`crate::ijvm_core::Runtime::call_stack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo<'a> {
+    /// The header of the method the frame belongs to, `None` for main.
+    pub method: Option<InstructionRef>,
+    /// The call the method returns to.
+    pub return_pc: InstructionRef,
+    /// Length of the operand stack below the frame's OBJREF.
+    pub base: usize,
+    /// Arguments first, starting with OBJREF, then the other locals.
+    pub locals: &'a [i32],
 }
 
 impl Frame {
-    pub fn new(starting_stack_length: u32, var_count: u32, restore_pc: InstructionRef) -> Frame {
+    pub fn new(
+        starting_stack_length: u32,
+        var_count: u32,
+        restore_pc: InstructionRef,
+        method: InstructionRef,
+    ) -> Frame {
         Frame {
             starting_stack_length,
             vars: TinyVars::new(var_count),
             restore_pc,
+            method: method as u32,
+        }
+    }
+
+    /// A frame whose locals are `vars`.
+    pub fn with_vars(
+        starting_stack_length: u32,
+        vars: Vec<i32>,
+        restore_pc: InstructionRef,
+        method: InstructionRef,
+    ) -> Frame {
+        Frame {
+            starting_stack_length,
+            vars: TinyVars::from_vec(vars),
+            restore_pc,
+            method: method as u32,
         }
     }
 
+    /// The frame's locals, arguments first.
+    #[inline]
+    pub fn vars(&self) -> &[i32] {
+        self.vars.as_slice()
+    }
+
+    #[inline]
+    pub fn vars_mut(&mut self) -> &mut [i32] {
+        self.vars.as_mut_slice()
+    }
+
     #[inline]
     pub fn load_var(&self, var: u16) -> i32 {
         self.vars.load_var(var)
@@ -77,11 +126,17 @@ impl Frame {
         self.starting_stack_length
     }
 
+    /// The header of the frame's method, 0 for main.
+    #[inline]
+    pub fn method(&self) -> InstructionRef {
+        self.method as InstructionRef
+    }
+
     #[inline]
     pub fn reset(
         &mut self,
         new_stack_length: u32,
-        new_var_count: u32,
+        _new_var_count: u32,
         new_restore_pc: InstructionRef,
     ) {
         self.vars.reset();
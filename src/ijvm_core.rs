@@ -1,16 +1,50 @@
-use std::io::Read;
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
 
 #[cfg(feature = "metrics")]
 use std::collections::HashMap;
 
 use crate::{
+    fuel::CostTable,
     ijvm,
-    instructions::{IJVMParser, MemoryBlock},
-    tiny::{FrameStack, Stack},
+    io::{self, ErrorKind, Read, Write},
+    native::{Native, NativeFn},
+    observer::{NoopObserver, Observer},
+    instructions::{IJVMParser, MemoryBlock, WideMemoryBlock},
+    snapshot::{self, FrameState, Snapshot, SnapshotError},
+    tiny::{FrameStack, Stack, StackSize},
+    trap::{Trap, TrapKind},
 };
+
+#[cfg(target_has_atomic = "ptr")]
+use crate::interrupt::InterruptHandle;
+#[cfg(feature = "green-threads")]
+use crate::{
+    snapshot::{ContextState, ThreadStatus, ThreadsState},
+    threads::{Context, ThreadId, Threads},
+};
+
 pub type Constant = i32;
 pub type InstructionRef = usize;
 
+/// Where `IN` reads from unless set otherwise: stdin, or nothing without std.
+#[cfg(feature = "std")]
+pub type DefaultInput = std::io::Stdin;
+#[cfg(not(feature = "std"))]
+pub type DefaultInput = &'static [u8];
+
+/// Where `OUT` writes to unless set otherwise: stderr, or a buffer without std.
+#[cfg(feature = "std")]
+pub type DefaultOutput = std::io::Stderr;
+#[cfg(not(feature = "std"))]
+pub type DefaultOutput = Vec<u8>;
+
+// frames listed by a CallDepthExceeded trap
+const BACKTRACE_LEN: usize = 16;
+
+// operand stack a spawned thread starts out with, it grows like main's
+#[cfg(feature = "green-threads")]
+const THREAD_STACK_SIZE: usize = 64;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConstantKind {
     None(Constant),
@@ -44,7 +78,7 @@ impl ConstantKind {
             ConstantKind::MethodRef(x) => *x,
             ConstantKind::Either(x) => *x,
             #[cfg(feature = "unsafe")]
-            _ => unsafe { std::hint::unreachable_unchecked() },
+            _ => unsafe { core::hint::unreachable_unchecked() },
             #[cfg(not(feature = "unsafe"))]
             _ => panic!("Not a method ref"),
         }
@@ -55,7 +89,7 @@ impl ConstantKind {
             ConstantKind::StackValue(x) => *x,
             ConstantKind::Either(x) => *x,
             #[cfg(feature = "unsafe")]
-            _ => unsafe { std::hint::unreachable_unchecked() },
+            _ => unsafe { core::hint::unreachable_unchecked() },
             #[cfg(not(feature = "unsafe"))]
             _ => panic!("Not a method ref"),
         }
@@ -82,28 +116,132 @@ impl ConstantKind {
     }
 }
 
-pub struct Runtime {
+/// What `IN` does once the input stream is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+    /// Push 0, as the reference implementation does.
+    #[default]
+    PushZero,
+    /// Push -1, like C's `getchar`.
+    PushMinusOne,
+    /// Stop execution with a [`TrapKind::EndOfInput`] trap.
+    Trap,
+}
+
+/// Where `IN` gets its bytes from when nothing has been fed with [`Runtime::feed_input`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputMode {
+    /// Block on the input stream.
+    #[default]
+    Blocking,
+    /// Never touch the input stream, pause with [`Outcome::NeedsInput`] instead.
+    /// The pause ends when the host feeds more bytes or closes the input.
+    NonBlocking,
+}
+
+/// What IADD, ISUB and IINC, and with `ext-arith` IMUL, IDIV and INEG, do when the
+/// result doesn't fit in an `i32`. IREM always fits, `i32::MIN % -1` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowMode {
+    /// Two's complement wraparound, as the IJVM spec says.
+    #[default]
+    Wrapping,
+    /// Stop execution with a [`TrapKind::Overflow`] trap.
+    Checked,
+    /// Clamp to `i32::MIN`/`i32::MAX`.
+    Saturating,
+}
+
+/// Why `step`/`steps`/`run` returned. The final top of stack and pc stay available
+/// through [`Runtime::tos`] and [`Runtime::program_counter`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The program can keep going.
+    Running,
+    /// The program executed `HALT`.
+    Halted,
+    /// Execution ran past the last instruction without a `HALT`.
+    EndOfProgram,
+    Trapped(Trap),
+    /// `IN` had nothing to read in [`InputMode::NonBlocking`]. Running again
+    /// re-executes the same `IN`.
+    NeedsInput,
+    /// The instruction budget ran out. Running again continues.
+    BudgetExhausted,
+    /// The next instruction has a breakpoint. Running again executes it and continues.
+    Breakpoint,
+    /// Stopped through an [`InterruptHandle`]. Running again continues.
+    Interrupted,
+}
+
+/// Names a method for [`Runtime::invoke`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodId<'a> {
+    /// Index of the constant holding the method's address, as `INVOKEVIRTUAL` names it.
+    Constant(u16),
+    /// Byte offset of the method header in the text.
+    Offset(u32),
+    /// A name given with [`Runtime::add_symbol`]. Binaries carry no names themselves.
+    Symbol(&'a str),
+}
+
+#[derive(Default)]
+struct InputState {
+    policy: EofPolicy,
+    mode: InputMode,
+    buffer: VecDeque<u8>,
+    closed: bool,
+    // bytes IN has consumed so far
+    consumed: u64,
+}
+
+/// An IJVM machine reading `IN` bytes from `R` and writing `OUT` bytes to `W`,
+/// reporting what it does to the [`Observer`] `O`.
+pub struct Runtime<R = DefaultInput, W = DefaultOutput, O = NoopObserver> {
     instructions: Vec<MemoryBlock>,
-    pub inner: RuntimeInner,
+    // instruction of every text byte
+    mappings: Vec<InstructionRef>,
+    // method names given by the host, with their header's byte offset
+    symbols: Vec<(String, u32)>,
+    // instructions replaced by a BREAKPOINT
+    breakpoints: Vec<(InstructionRef, MemoryBlock)>,
+    // the breakpoint at the pc already stopped execution, and its instruction
+    // paused before completing
+    breakpoint_taken: bool,
+    costs: CostTable,
+    fuel_used: u64,
+    pub inner: RuntimeInner<R, W, O>,
 }
 
-pub struct RuntimeInner {
+pub struct RuntimeInner<R = DefaultInput, W = DefaultOutput, O = NoopObserver> {
     instructions: Vec<MemoryBlock>,
     constants: Vec<Constant>,
+    // fingerprint of the loaded binary, ties snapshots to it
+    program: u64,
     frames: FrameStack,
     program_counter: usize, // counter over instructions, not original bytes
     is_finished: bool,
+    outcome: Outcome,
     stack: Stack,
-    pub out_stream: std::io::Stderr,
-    in_stream: std::io::Stdin,
+    pub out_stream: W,
+    in_stream: R,
+    input: InputState,
+    overflow: OverflowMode,
+    #[cfg(target_has_atomic = "ptr")]
+    interrupt: InterruptHandle,
+    observer: O,
+    natives: Vec<Native<R, W, O>>,
+    // the threads that aren't running, the running one's state is above
+    #[cfg(feature = "green-threads")]
+    threads: Threads,
 
     #[cfg(feature = "metrics")]
     pub metrics: Metrics,
 }
 
-impl Runtime {
+impl<R: Read, W: Write, O: Observer> Runtime<R, W, O> {
     #[inline]
-    pub fn step(&mut self) {
+    fn step_inner(&mut self) {
         let instruction;
         #[cfg(feature = "unsafe")]
         unsafe {
@@ -132,44 +270,542 @@ impl Runtime {
         //     &self.inner.stack.stack[1..self.inner.stack.len() + 1]
         // );
 
-        instruction.execute(&mut self.inner);
+        #[cfg(feature = "green-threads")]
+        self.inner.threads.tick();
+
+        if O::ENABLED {
+            let pc = self.inner.program_counter;
+            self.inner.observer.before_instruction(pc, instruction);
+            instruction.execute(&mut self.inner);
+            self.inner.observer.after_instruction(pc, instruction);
+        } else {
+            instruction.execute(&mut self.inner);
+        }
+
+        // an instruction that switched threads started a new slice
+        #[cfg(feature = "green-threads")]
+        if self.inner.threads.expired() && !self.inner.is_finished {
+            self.inner.switch_thread();
+        }
+        self.inner.advance();
+    }
+
+    #[inline]
+    pub fn step(&mut self) -> Outcome {
+        if !self.resume() && !self.inner.is_finished {
+            self.step_inner();
+        }
+        self.outcome()
+    }
+    /// Executes up to `count` instructions, stopping early if execution stops.
+    pub fn steps(&mut self, count: usize) -> Outcome {
+        for _ in 0..count {
+            if self.step() != Outcome::Running {
+                break;
+            }
+        }
+        self.outcome()
+    }
+    #[inline]
+    pub fn run(&mut self) -> Outcome {
+        self.resume();
+        while !self.inner.is_finished {
+            self.step_inner();
+        }
+        self.outcome()
+    }
+
+    /// Runs until execution stops or the next instruction costs more than the
+    /// remaining `fuel`, see [`Runtime::set_cost_table`]. Running out stops with
+    /// [`Outcome::BudgetExhausted`], after which any `run` continues.
+    pub fn run_with_fuel(&mut self, fuel: u64) -> Outcome {
+        self.run_with_fuel_until(fuel, |_| false)
+    }
+
+    /// [`Runtime::run_with_fuel`], but also returns, still running, after any
+    /// instruction once `until` holds.
+    #[inline]
+    pub(crate) fn run_with_fuel_until(
+        &mut self,
+        mut fuel: u64,
+        until: impl Fn(&Self) -> bool,
+    ) -> Outcome {
+        // resuming may execute the instruction under a breakpoint, which costs the same
+        if let Some(cost) = self.breakpoint_cost() {
+            if cost > fuel {
+                // still taken, it executes once there's fuel for it
+                self.breakpoint_taken = true;
+                self.inner.stop(Outcome::BudgetExhausted);
+                return self.outcome();
+            }
+            if self.resume() && self.inner.outcome != Outcome::NeedsInput {
+                fuel -= cost;
+                self.fuel_used += cost;
+            }
+        } else {
+            self.resume();
+        }
+        while !self.inner.is_finished {
+            let cost = self.cost_at(self.inner.program_counter);
+            if cost > fuel {
+                self.inner.stop(Outcome::BudgetExhausted);
+                break;
+            }
+            self.step_inner();
+
+            // an instruction that will execute again is charged when it does
+            if !matches!(
+                self.inner.outcome,
+                Outcome::NeedsInput | Outcome::Breakpoint
+            ) {
+                fuel -= cost;
+                self.fuel_used += cost;
+            }
+            if until(self) {
+                break;
+            }
+        }
+        self.outcome()
+    }
+
+    /// Calls a method with `args`, which don't include OBJREF, and returns what it
+    /// returns. The method runs on top of the current state, which is back as it
+    /// was afterwards, whether the call returned or not.
+    ///
+    /// A method that can't be found or takes a different number of arguments fails
+    /// with a [`TrapKind::BadMethod`] trap. One that stops without returning fails
+    /// with its trap, or [`TrapKind::NoReturn`] when it didn't trap. Breakpoints
+    /// are ignored, and so is an interrupt requested before the call, which stays
+    /// pending for the next run. With green threads, the method runs on the current thread
+    /// alone: `YIELD` does nothing and `JOIN` of a running thread traps with
+    /// [`TrapKind::Deadlock`].
+    pub fn invoke(&mut self, method: MethodId, args: &[i32]) -> Result<i32, Trap> {
+        let header = self
+            .resolve_method(method)
+            .ok_or_else(|| self.invoke_trap(TrapKind::BadMethod))?;
+        let (n_args, n_vars, native) = match self.inner.instructions[header] {
+            MemoryBlock::METHODHEADER { n_args, n_vars } => (n_args, n_vars, None),
+            MemoryBlock::NATIVE { native, n_args } => (n_args, 0, Some(native)),
+            _ => return Err(self.invoke_trap(TrapKind::BadMethod)),
+        };
+        if n_args as usize != args.len() + 1 {
+            return Err(self.invoke_trap(TrapKind::BadMethod));
+        }
+
+        let program_counter = self.inner.program_counter;
+        let outcome = core::mem::replace(&mut self.inner.outcome, Outcome::Running);
+        let is_finished = core::mem::replace(&mut self.inner.is_finished, false);
+        let breakpoint_taken = core::mem::replace(&mut self.breakpoint_taken, false);
+        // a request made before the call is for the caller's run
+        #[cfg(target_has_atomic = "ptr")]
+        let interrupted = self.inner.interrupt.take();
+        // the method can pop below its frame, into the caller's values
+        let stack = self.inner.stack.stack_slice().to_vec();
+        let top_value = self.inner.stack.peek_top();
+        let depth = self.inner.frames.depth();
+        #[cfg(feature = "green-threads")]
+        let pinned = core::mem::replace(&mut self.inner.threads.pinned, true);
+
+        // OBJREF
+        self.inner.stack_push(0);
+        for arg in args {
+            self.inner.stack_push(*arg);
+        }
+        if let Some(native) = native {
+            if !self.inner.is_finished {
+                let _ = self.inner.call_native(native, n_args);
+            }
+        } else if !self.inner.is_finished && self.inner.push_frame(header, n_vars, n_args) {
+            self.inner.set_pc(header + 1);
+        }
+        while self.inner.frames.depth() > depth {
+            if !self.inner.is_finished {
+                self.step_inner();
+            } else if self.inner.outcome == Outcome::Breakpoint {
+                self.resume();
+            } else {
+                break;
+            }
+        }
+
+        let result = match &self.inner.outcome {
+            Outcome::Running => Ok(self.inner.visit_stack().peek_top()),
+            Outcome::Trapped(trap) => Err(trap.clone()),
+            stopped => {
+                let kind = TrapKind::NoReturn(Box::new(stopped.clone()));
+                Err(self.invoke_trap(kind))
+            }
+        };
+
+        self.inner.frames.truncate(depth);
+        self.inner.stack.restore(&stack, top_value);
+        self.inner.program_counter = program_counter;
+        self.inner.outcome = outcome;
+        self.inner.is_finished = is_finished;
+        self.breakpoint_taken = breakpoint_taken;
+        #[cfg(target_has_atomic = "ptr")]
+        if interrupted {
+            self.inner.interrupt.interrupt();
+        }
+        #[cfg(feature = "green-threads")]
+        {
+            self.inner.threads.pinned = pinned;
+        }
+        result
+    }
+
+    /// Makes calls to `method` run `function` instead of the method's code, which
+    /// can be anything; a stub that just executes `ERR` is the convention for
+    /// methods only meant to be native. `INVOKEVIRTUAL` pops the arguments and
+    /// OBJREF and pushes the return value, like for an IJVM method. Returns false
+    /// if `method` doesn't name a method.
+    pub fn register_native(
+        &mut self,
+        method: MethodId,
+        function: impl FnMut(&mut RuntimeInner<R, W, O>, &[i32]) -> i32 + Send + 'static,
+    ) -> bool {
+        let Some(header) = self.resolve_method(method) else {
+            return false;
+        };
+        let function: NativeFn<R, W, O> = Box::new(function);
+
+        if let MemoryBlock::NATIVE { native, .. } = self.instructions[header] {
+            self.inner.natives[native].function = Some(function);
+            return true;
+        }
+        let MemoryBlock::METHODHEADER { n_args, .. } = self.instructions[header] else {
+            return false;
+        };
+        let native = MemoryBlock::NATIVE {
+            native: self.inner.natives.len(),
+            n_args,
+        };
+        self.inner.natives.push(Native {
+            header,
+            original: self.instructions[header].clone(),
+            function: Some(function),
+        });
+        self.instructions[header] = native.clone();
+        self.inner.instructions[header] = native;
+        true
+    }
+
+    /// Names the method whose header is at `byte_offset`, for [`MethodId::Symbol`].
+    pub fn add_symbol(&mut self, name: &str, byte_offset: u32) {
+        self.symbols.retain(|(known, _)| known != name);
+        self.symbols.push((String::from(name), byte_offset));
+    }
+
+    /// The index of a method's header, if `method` names one.
+    fn resolve_method(&self, method: MethodId) -> Option<InstructionRef> {
+        let offset = match method {
+            MethodId::Constant(ind) => {
+                u32::try_from(*self.inner.constants.get(ind as usize)?).ok()?
+            }
+            MethodId::Offset(offset) => offset,
+            MethodId::Symbol(name) => {
+                self.symbols
+                    .iter()
+                    .find(|(known, _)| known == name)?
+                    .1
+            }
+        } as usize;
+
+        // the offset has to be where an instruction starts
+        let header = *self.mappings.get(offset)?;
+        if offset > 0 && self.mappings[offset - 1] == header {
+            return None;
+        }
+        // without breakpoints, which may sit on the header
+        matches!(
+            self.inner.instructions[header],
+            MemoryBlock::METHODHEADER { .. } | MemoryBlock::NATIVE { .. }
+        )
+        .then_some(header)
+    }
+
+    /// A trap for a failed [`Runtime::invoke`], which leaves the runtime as it was.
+    fn invoke_trap(&self, kind: TrapKind) -> Trap {
+        Trap {
+            kind,
+            pc: self.inner.program_counter,
+            call_depth: self.inner.frames.depth(),
+        }
+    }
+
+    /// A handle other threads can use to stop this runtime.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.inner.interrupt.clone()
+    }
+
+    pub fn set_cost_table(&mut self, costs: CostTable) {
+        self.costs = costs;
+    }
+
+    /// Fuel charged by [`Runtime::run_with_fuel`] since loading or the last reset.
+    /// With the default [`CostTable`] this is the number of instructions it executed.
+    pub fn fuel_used(&self) -> u64 {
+        self.fuel_used
+    }
+
+    /// The cost of the instruction under a breakpoint that resuming executes, if it
+    /// executes one.
+    fn breakpoint_cost(&self) -> Option<u64> {
+        let taken = match self.inner.outcome {
+            Outcome::Breakpoint => true,
+            Outcome::NeedsInput | Outcome::BudgetExhausted | Outcome::Interrupted => {
+                self.breakpoint_taken
+            }
+            _ => false,
+        };
+        if !taken {
+            return None;
+        }
+        self.original_cost(self.inner.program_counter)
+    }
+
+    /// The cost of the instruction at `pc`. A breakpoint costs what the instruction
+    /// under it does.
+    #[inline]
+    fn cost_at(&self, pc: InstructionRef) -> u64 {
+        let instruction;
         #[cfg(feature = "unsafe")]
         unsafe {
-            self.inner.program_counter = self.inner.program_counter.unchecked_add(1);
+            instruction = self.instructions.get_unchecked(pc);
         }
         #[cfg(not(feature = "unsafe"))]
         {
-            self.inner.program_counter += 1;
+            instruction = &self.instructions[pc];
+        }
+        match instruction {
+            MemoryBlock::BREAKPOINT => self.original_cost(pc).unwrap_or(0),
+            instruction => self.costs.cost(instruction),
         }
+    }
 
-        // this check has to be present for tests, as they dont HALT correctly
-        #[cfg(not(feature = "unsafe"))]
-        if self.program_counter() >= self.instructions.len() {
-            self.inner.is_finished = true;
+    /// The cost of the instruction under the breakpoint at `pc`, if there is one.
+    #[cold]
+    fn original_cost(&self, pc: InstructionRef) -> Option<u64> {
+        self.breakpoints
+            .iter()
+            .find(|(at, _)| *at == pc)
+            .map(|(_, original)| self.costs.cost(original))
+    }
+
+    /// Clears a pause so the next `step`/`run` continues where it left off. Returns
+    /// whether that took executing the instruction under a breakpoint.
+    #[inline]
+    fn resume(&mut self) -> bool {
+        match self.inner.outcome {
+            Outcome::NeedsInput | Outcome::BudgetExhausted | Outcome::Interrupted => {
+                self.inner.is_finished = false;
+                self.inner.outcome = Outcome::Running;
+                // an instruction that paused under a breakpoint doesn't stop on it twice
+                if !self.breakpoint_taken {
+                    return false;
+                }
+            }
+            Outcome::Breakpoint => {
+                self.inner.is_finished = false;
+                self.inner.outcome = Outcome::Running;
+            }
+            _ => return false,
         }
+        self.breakpoint_taken = false;
+
+        // the breakpoint may be gone by now, e.g. after a restore
+        let pc = self.inner.program_counter;
+        let Some((_, original)) = self.breakpoints.iter().find(|(at, _)| *at == pc).cloned()
+        else {
+            return false;
+        };
+        self.inner.observer.before_instruction(pc, &original);
+        original.execute(&mut self.inner);
+        self.inner.observer.after_instruction(pc, &original);
+        self.inner.advance();
+        // IN waiting for input executes again
+        self.breakpoint_taken = self.inner.outcome == Outcome::NeedsInput;
+        true
     }
-    pub fn steps(&mut self, count: usize) {
-        for _ in 0..count {
-            self.step();
+
+    /// Makes execution stop with [`Outcome::Breakpoint`] before executing the instruction at `pc`.
+    pub fn set_breakpoint(&mut self, pc: InstructionRef) {
+        if self.breakpoints.iter().any(|(at, _)| *at == pc) || pc >= self.visit_instructions().len() {
+            return;
+        }
+        let original = core::mem::replace(&mut self.instructions[pc], MemoryBlock::BREAKPOINT);
+        self.breakpoints.push((pc, original));
+    }
+
+    pub fn clear_breakpoint(&mut self, pc: InstructionRef) {
+        if let Some(ind) = self.breakpoints.iter().position(|(at, _)| *at == pc) {
+            let (_, original) = self.breakpoints.swap_remove(ind);
+            self.instructions[pc] = original;
         }
     }
+
     #[inline]
-    pub fn run(&mut self) {
-        while !self.inner.is_finished {
-            self.step();
+    pub fn outcome(&self) -> Outcome {
+        self.inner.outcome.clone()
+    }
+
+    /// The trap that stopped execution, if any.
+    pub fn trap(&self) -> Option<&Trap> {
+        match &self.inner.outcome {
+            Outcome::Trapped(trap) => Some(trap),
+            _ => None,
+        }
+    }
+
+    pub fn set_eof_policy(&mut self, policy: EofPolicy) {
+        self.inner.input.policy = policy;
+    }
+
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.inner.input.mode = mode;
+    }
+
+    pub fn input_mode(&self) -> InputMode {
+        self.inner.input.mode
+    }
+
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.inner.overflow = mode;
+    }
+
+    /// Limits how deep methods can call, calling deeper traps with
+    /// [`TrapKind::CallDepthExceeded`]. Defaults to [`crate::tiny::DEFAULT_MAX_CALL_DEPTH`].
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.inner.frames.set_max_depth(depth);
+    }
+
+    /// Sets how many instructions a green thread runs before the next one gets its
+    /// turn, unless it yields or waits first. Defaults to
+    /// [`crate::threads::DEFAULT_TIME_SLICE`].
+    #[cfg(feature = "green-threads")]
+    pub fn set_time_slice(&mut self, instructions: u32) {
+        self.inner.threads.set_time_slice(instructions);
+    }
+
+    /// The green thread that is running, or runs next.
+    #[cfg(feature = "green-threads")]
+    pub fn thread_id(&self) -> ThreadId {
+        self.inner.thread_id()
+    }
+
+    /// Queues bytes for `IN`. They are consumed before the input stream is touched.
+    pub fn feed_input(&mut self, bytes: &[u8]) {
+        self.inner.input.buffer.extend(bytes);
+    }
+
+    /// Marks the end of fed input, so a non-blocking `IN` applies the [`EofPolicy`]
+    /// instead of waiting once the queue runs dry.
+    pub fn close_input(&mut self) {
+        self.inner.input.closed = true;
+    }
+
+    /// Captures the machine state: stack, frames, pc, outcome and input position.
+    /// With green threads, that of every thread.
+    pub fn snapshot(&self) -> Snapshot {
+        let inner = &self.inner;
+        Snapshot {
+            program: inner.program,
+            program_counter: inner.program_counter,
+            outcome: inner.outcome.clone(),
+            stack: inner.stack.stack_slice().to_vec(),
+            top_value: inner.stack.peek_top(),
+            frames: frame_states(&inner.frames),
+            input_consumed: inner.input.consumed,
+            input_pending: inner.input.buffer.iter().copied().collect(),
+            input_closed: inner.input.closed,
+            breakpoint_taken: self.breakpoint_taken,
+            #[cfg(feature = "green-threads")]
+            threads: inner.threads.save(|context| ContextState {
+                program_counter: context.program_counter,
+                stack: context.stack.stack_slice().to_vec(),
+                top_value: context.stack.peek_top(),
+                frames: frame_states(&context.frames),
+            }),
+            #[cfg(not(feature = "green-threads"))]
+            threads: None,
         }
     }
 
+    /// Puts the machine back into the state of `snapshot`, which has to come from
+    /// the same program. On error the runtime is left untouched. Green threads
+    /// are replaced by the snapshot's; a build without them can't restore a
+    /// snapshot taken after the program spawned one.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.program != self.inner.program {
+            return Err(SnapshotError::ProgramMismatch);
+        }
+        let main_var_count = self.inner.main_var_count();
+        let current = snapshot.threads.as_ref().map_or(0, |threads| threads.current);
+        self.inner.check_thread(
+            snapshot.program_counter,
+            &snapshot.stack,
+            &snapshot.frames,
+            if current == 0 { main_var_count } else { 0 },
+        )?;
+        #[cfg(feature = "green-threads")]
+        if let Some(threads) = &snapshot.threads {
+            self.inner.check_threads(threads, main_var_count)?;
+        }
+        #[cfg(not(feature = "green-threads"))]
+        if snapshot.threads.is_some() {
+            return Err(SnapshotError::Corrupt("threads"));
+        }
+
+        #[cfg(feature = "green-threads")]
+        {
+            self.inner.end_threads();
+            if let Some(threads) = &snapshot.threads {
+                self.inner.restore_threads(threads, main_var_count);
+            }
+        }
+
+        self.breakpoint_taken = snapshot.breakpoint_taken;
+        let inner = &mut self.inner;
+        let frames = inner.frames_from(&snapshot.frames);
+        inner.program_counter = snapshot.program_counter;
+        inner.is_finished = snapshot.outcome != Outcome::Running;
+        inner.outcome = snapshot.outcome.clone();
+        inner.stack.restore(&snapshot.stack, snapshot.top_value);
+        inner.frames.replace(frames);
+        inner.input.consumed = snapshot.input_consumed;
+        inner.input.buffer = snapshot.input_pending.iter().copied().collect();
+        inner.input.closed = snapshot.input_closed;
+        Ok(())
+    }
+
+    /// The program's instructions, without the end of program marker.
     #[inline]
-    pub fn visit_instructions(&self) -> &Vec<MemoryBlock> {
-        &self.instructions
+    pub fn visit_instructions(&self) -> &[MemoryBlock] {
+        &self.inner.instructions[..self.inner.instructions.len() - 1]
     }
 
+    /// Puts the machine back into its state right after loading, for running the
+    /// program again. Settings stay as they are: the I/O streams, input mode, EOF
+    /// and overflow policies, cost table and breakpoints. Green threads other than
+    /// main end.
     pub fn reset(&mut self) {
+        #[cfg(feature = "green-threads")]
+        self.inner.end_threads();
+
+        self.fuel_used = 0;
+        self.breakpoint_taken = false;
         self.inner.program_counter = 0;
         self.inner.stack.clear();
         self.inner.frames.clear();
         self.inner.is_finished = false;
+        self.inner.outcome = Outcome::Running;
+        self.inner.input.buffer.clear();
+        self.inner.input.closed = false;
+        self.inner.input.consumed = 0;
+        // a request aimed at the previous run
+        #[cfg(target_has_atomic = "ptr")]
+        self.inner.interrupt.take();
 
         #[cfg(feature = "metrics")]
         {
@@ -193,21 +829,232 @@ impl Runtime {
     pub fn program_counter(&self) -> usize {
         self.inner.program_counter()
     }
+
+    /// The program counter as a byte offset into the text, the way the reference
+    /// implementation counts it.
+    pub fn program_offset(&self) -> usize {
+        let pc = self.inner.program_counter;
+        self.mappings.partition_point(|instruction| *instruction < pc)
+    }
     #[inline]
     pub fn frame(&mut self) -> &ijvm::Frame {
         self.inner.frames.current_frame()
     }
+
+    /// Every frame, from the current one out to main.
+    pub fn call_stack(&self) -> impl Iterator<Item = ijvm::FrameInfo<'_>> {
+        let depth = self.inner.frames.depth();
+        self.inner
+            .frames
+            .iter()
+            .rev()
+            .enumerate()
+            .map(move |(level, frame)| ijvm::FrameInfo {
+                method: (level < depth).then_some(frame.method()),
+                return_pc: frame.restore_pc(),
+                base: frame.starting_stack_length() as usize,
+                locals: frame.vars(),
+            })
+    }
+
+    /// The locals of the frame `level` frames out from the current one, for
+    /// changing them. `None` past main.
+    pub fn locals_mut(&mut self, level: usize) -> Option<&mut [i32]> {
+        Some(self.inner.frames.get_mut(level)?.vars_mut())
+    }
+
+    /// The live operand stack, bottom first.
+    #[inline]
+    pub fn stack_slice(&self) -> &[i32] {
+        self.inner.stack.stack_slice()
+    }
 }
 
-impl RuntimeInner {
+impl<R, W, O> Runtime<R, W, O> {
+    /// Replaces the stream `IN` reads from. This drops registered natives, which
+    /// are tied to the runtime's type, so register them afterwards.
+    pub fn with_input<R2: Read>(self, input: R2) -> Runtime<R2, W, O> {
+        self.map_parts(|_, out_stream, observer| (input, out_stream, observer))
+    }
+
+    /// Replaces the stream `OUT` writes to. Drops registered natives, like
+    /// [`Runtime::with_input`].
+    pub fn with_output<W2: Write>(self, output: W2) -> Runtime<R, W2, O> {
+        self.map_parts(|in_stream, _, observer| (in_stream, output, observer))
+    }
+
+    /// Replaces the observer. Drops registered natives, like [`Runtime::with_input`].
+    pub fn with_observer<O2: Observer>(self, observer: O2) -> Runtime<R, W, O2> {
+        self.map_parts(|in_stream, out_stream, _| (in_stream, out_stream, observer))
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.inner.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.inner.observer
+    }
+
+    fn map_parts<R2, W2, O2>(
+        mut self,
+        f: impl FnOnce(R, W, O) -> (R2, W2, O2),
+    ) -> Runtime<R2, W2, O2> {
+        // natives take the old types, so their methods go back to being IJVM code
+        for native in core::mem::take(&mut self.inner.natives) {
+            self.instructions[native.header] = native.original.clone();
+            self.inner.instructions[native.header] = native.original;
+        }
+
+        let RuntimeInner {
+            instructions,
+            constants,
+            program,
+            frames,
+            program_counter,
+            is_finished,
+            outcome,
+            stack,
+            out_stream,
+            in_stream,
+            input,
+            overflow,
+            #[cfg(target_has_atomic = "ptr")]
+            interrupt,
+            observer,
+            natives: _,
+            #[cfg(feature = "green-threads")]
+            threads,
+            #[cfg(feature = "metrics")]
+            metrics,
+        } = self.inner;
+        let (in_stream, out_stream, observer) = f(in_stream, out_stream, observer);
+        Runtime {
+            instructions: self.instructions,
+            mappings: self.mappings,
+            symbols: self.symbols,
+            breakpoints: self.breakpoints,
+            breakpoint_taken: self.breakpoint_taken,
+            costs: self.costs,
+            fuel_used: self.fuel_used,
+            inner: RuntimeInner {
+                instructions,
+                constants,
+                program,
+                frames,
+                program_counter,
+                is_finished,
+                outcome,
+                stack,
+                out_stream,
+                in_stream,
+                input,
+                overflow,
+                #[cfg(target_has_atomic = "ptr")]
+                interrupt,
+                observer,
+                natives: Vec::new(),
+                #[cfg(feature = "green-threads")]
+                threads,
+                #[cfg(feature = "metrics")]
+                metrics,
+            },
+        }
+    }
+}
+
+impl<R: Read, W: Write, O: Observer> RuntimeInner<R, W, O> {
+    /// Continues after the instruction at `pc`. The checked build traps with
+    /// [`TrapKind::BadBranch`] if there is no instruction there.
     #[inline]
     pub fn set_pc(&mut self, pc: InstructionRef) {
+        #[cfg(feature = "checked")]
+        if !self.is_branch_target(pc) {
+            return self.trap(TrapKind::BadBranch);
+        }
         self.program_counter = pc;
     }
 
+    // `pc` wraps around for instruction 0
+    #[cfg(feature = "checked")]
+    #[inline]
+    fn is_branch_target(&self, pc: InstructionRef) -> bool {
+        pc.wrapping_add(1) < self.instructions.len()
+    }
+
+    /// Jumps to the instruction after `pc`. Backward jumps, including to the jump
+    /// itself, honour interrupt requests.
+    #[inline]
+    pub fn jump(&mut self, pc: InstructionRef) {
+        #[cfg(feature = "checked")]
+        if !self.is_branch_target(pc) {
+            return self.trap(TrapKind::BadBranch);
+        }
+        // `pc` wraps around for a jump to instruction 0
+        if pc.wrapping_add(1) <= self.program_counter {
+            self.check_interrupt();
+        }
+        if O::ENABLED {
+            self.observer.on_branch(self.program_counter, pc.wrapping_add(1));
+        }
+        self.program_counter = pc;
+    }
+
+    /// Moves past the instruction that just executed. One that trapped stays put,
+    /// so the pc is where the trap happened.
+    #[inline]
+    fn advance(&mut self) {
+        if self.is_finished && matches!(self.outcome, Outcome::Trapped(_)) {
+            return;
+        }
+        // wrapping, because an instruction may rewind the pc to before 0 to re-execute itself
+        self.program_counter = self.program_counter.wrapping_add(1);
+    }
+
+    /// Stops with [`Outcome::Interrupted`] if an interrupt was requested. Execution
+    /// continues after the current instruction once resumed. An instruction that
+    /// already stopped, e.g. with a trap, keeps its outcome and leaves the request.
+    /// Without pointer-sized atomics there are no interrupts, and this does nothing.
+    #[inline]
+    pub fn check_interrupt(&mut self) {
+        #[cfg(target_has_atomic = "ptr")]
+        if !self.is_finished && self.interrupt.take() {
+            self.stop(Outcome::Interrupted);
+        }
+    }
+
+    /// Values the current frame can pop. The checked build keeps it off its
+    /// caller's values, the others only stop at the bottom of the stack.
+    #[inline]
+    fn poppable(&self) -> usize {
+        #[cfg(feature = "checked")]
+        return self.stack.len().saturating_sub(self.frames.base());
+        #[cfg(not(feature = "checked"))]
+        self.stack.len()
+    }
+
+    /// Pops the top value, or traps with [`TrapKind::StackUnderflow`] and returns
+    /// None if there is nothing to pop.
+    #[inline]
+    pub fn stack_pop(&mut self) -> Option<i32> {
+        if self.poppable() == 0 {
+            self.trap(TrapKind::StackUnderflow);
+            return None;
+        }
+
+        Some(self.stack.pop())
+    }
+
+    /// Pops the top two values, top first. Traps with [`TrapKind::StackUnderflow`]
+    /// and leaves the stack as it was if there aren't two to pop.
     #[inline]
-    pub fn stack_pop(&mut self) -> i32 {
-        self.stack.pop()
+    pub fn stack_pop2(&mut self) -> Option<(i32, i32)> {
+        if self.poppable() < 2 {
+            self.trap(TrapKind::StackUnderflow);
+            return None;
+        }
+
+        Some((self.stack.pop(), self.stack.pop()))
     }
 
     #[inline]
@@ -217,7 +1064,94 @@ impl RuntimeInner {
 
     #[inline]
     pub fn stack_push(&mut self, value: i32) {
-        self.stack.push(value);
+        if !self.stack.push(value) {
+            self.trap(TrapKind::StackOverflow);
+        }
+    }
+
+    /// Adds according to the [`OverflowMode`]. A checked overflow traps and returns None.
+    #[inline]
+    pub fn add(&mut self, a: i32, b: i32) -> Option<i32> {
+        match self.overflow {
+            OverflowMode::Wrapping => Some(a.wrapping_add(b)),
+            OverflowMode::Saturating => Some(a.saturating_add(b)),
+            OverflowMode::Checked => {
+                let result = a.checked_add(b);
+                if result.is_none() {
+                    self.trap(TrapKind::Overflow);
+                }
+                result
+            }
+        }
+    }
+
+    /// Subtracts according to the [`OverflowMode`]. A checked overflow traps and returns None.
+    #[inline]
+    pub fn sub(&mut self, a: i32, b: i32) -> Option<i32> {
+        match self.overflow {
+            OverflowMode::Wrapping => Some(a.wrapping_sub(b)),
+            OverflowMode::Saturating => Some(a.saturating_sub(b)),
+            OverflowMode::Checked => {
+                let result = a.checked_sub(b);
+                if result.is_none() {
+                    self.trap(TrapKind::Overflow);
+                }
+                result
+            }
+        }
+    }
+
+    /// Multiplies according to the [`OverflowMode`]. A checked overflow traps and returns None.
+    #[cfg(feature = "ext-arith")]
+    #[inline]
+    pub fn mul(&mut self, a: i32, b: i32) -> Option<i32> {
+        match self.overflow {
+            OverflowMode::Wrapping => Some(a.wrapping_mul(b)),
+            OverflowMode::Saturating => Some(a.saturating_mul(b)),
+            OverflowMode::Checked => {
+                let result = a.checked_mul(b);
+                if result.is_none() {
+                    self.trap(TrapKind::Overflow);
+                }
+                result
+            }
+        }
+    }
+
+    /// Divides according to the [`OverflowMode`], which only matters for
+    /// `i32::MIN / -1`. `b` isn't 0. A checked overflow traps and returns None.
+    #[cfg(feature = "ext-arith")]
+    #[inline]
+    pub fn div(&mut self, a: i32, b: i32) -> Option<i32> {
+        match self.overflow {
+            OverflowMode::Wrapping => Some(a.wrapping_div(b)),
+            OverflowMode::Saturating => Some(a.saturating_div(b)),
+            OverflowMode::Checked => {
+                let result = a.checked_div(b);
+                if result.is_none() {
+                    self.trap(TrapKind::Overflow);
+                }
+                result
+            }
+        }
+    }
+
+    /// Negates according to the [`OverflowMode`], which only matters for `i32::MIN`.
+    /// A checked overflow traps and returns None.
+    #[cfg(feature = "ext-arith")]
+    #[inline]
+    pub fn neg(&mut self, a: i32) -> Option<i32> {
+        match self.overflow {
+            OverflowMode::Wrapping => Some(a.wrapping_neg()),
+            OverflowMode::Saturating => Some(a.saturating_neg()),
+            OverflowMode::Checked => {
+                let result = a.checked_neg();
+                if result.is_none() {
+                    self.trap(TrapKind::Overflow);
+                }
+                result
+            }
+        }
     }
 
     #[inline]
@@ -238,27 +1172,130 @@ impl RuntimeInner {
         //     *self.stack.get_unchecked_mut(len - 2) = a;
         // }
         // #[cfg(not(feature = "unsafe"))]
-        {
-            let a = self.stack_pop();
-            let b = self.stack_pop();
+        if let Some((a, b)) = self.stack_pop2() {
             self.stack_push(a);
             self.stack_push(b);
         }
     }
 
     #[inline]
-    pub fn in_stream(&mut self) -> &mut std::io::Stdin {
+    pub fn in_stream(&mut self) -> &mut R {
         &mut self.in_stream
     }
 
     #[inline]
-    pub fn out_stream(&mut self) -> &mut std::io::Stderr {
+    pub fn out_stream(&mut self) -> &mut W {
         &mut self.out_stream
     }
 
     #[inline]
     pub fn halt(&mut self) {
+        self.stop(Outcome::Halted);
+    }
+
+    /// Executes the marker after the last instruction. The pc stays on it. A spawned
+    /// green thread ends here instead, with the value on top of its stack.
+    #[cold]
+    pub fn end_of_program(&mut self) {
+        #[cfg(feature = "green-threads")]
+        if self.threads.current() != 0 && !self.threads.pinned {
+            let value = self.stack.peek_top();
+            self.threads.finish(value);
+            return self.switch_thread();
+        }
+
+        self.program_counter = self.program_counter.wrapping_sub(1);
+        self.stop(Outcome::EndOfProgram);
+    }
+
+    /// Stops before the current instruction, which sits under a breakpoint.
+    #[cold]
+    pub fn hit_breakpoint(&mut self) {
+        self.program_counter = self.program_counter.wrapping_sub(1);
+        self.stop(Outcome::Breakpoint);
+    }
+
+    #[inline]
+    pub fn stop(&mut self, outcome: Outcome) {
         self.is_finished = true;
+        self.outcome = outcome;
+    }
+
+    /// Stops execution with a trap at the current instruction, which then does
+    /// nothing more. The pc stays on it.
+    #[cold]
+    pub fn trap(&mut self, kind: TrapKind) {
+        // only the first trap counts
+        if matches!(self.outcome, Outcome::Trapped(_)) {
+            return;
+        }
+        let trap = Trap {
+            kind,
+            pc: self.program_counter,
+            call_depth: self.frames.depth(),
+        };
+        if O::ENABLED {
+            self.observer.on_trap(&trap);
+        }
+        self.stop(Outcome::Trapped(trap));
+    }
+
+    /// Pauses on the current instruction, `run` will execute it again once resumed.
+    #[inline]
+    pub fn wait_for_input(&mut self) {
+        self.program_counter = self.program_counter.wrapping_sub(1);
+        self.stop(Outcome::NeedsInput);
+    }
+
+    /// Executes `IN`: pushes the next input byte, or whatever the [`EofPolicy`] says at the end.
+    pub fn read_input(&mut self) {
+        let byte = match self.input.buffer.pop_front() {
+            Some(byte) => Some(byte),
+            None if self.input.mode == InputMode::NonBlocking => {
+                if !self.input.closed {
+                    self.wait_for_input();
+                    return;
+                }
+                None
+            }
+            None => {
+                let mut loaded = [0u8; 1];
+                loop {
+                    match self.in_stream.read(&mut loaded) {
+                        Ok(n_loaded) => break (n_loaded > 0).then_some(loaded[0]),
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        // a non-blocking stream pauses just like InputMode::NonBlocking
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            return self.wait_for_input()
+                        }
+                        Err(e) => return self.trap(TrapKind::Io(e.kind())),
+                    }
+                }
+            }
+        };
+
+        if O::ENABLED {
+            self.observer.on_input(byte);
+        }
+        match (byte, self.input.policy) {
+            (Some(byte), _) => {
+                self.input.consumed += 1;
+                self.stack_push(byte as i32)
+            }
+            (None, EofPolicy::PushZero) => self.stack_push(0),
+            (None, EofPolicy::PushMinusOne) => self.stack_push(-1),
+            (None, EofPolicy::Trap) => self.trap(TrapKind::EndOfInput),
+        }
+    }
+
+    /// Executes `OUT`.
+    pub fn write_output(&mut self, byte: u8) {
+        if O::ENABLED {
+            self.observer.on_output(byte);
+        }
+        if let Err(e) = self.out_stream.write_all(&[byte]) {
+            self.trap(TrapKind::Io(e.kind()));
+        }
     }
 
     #[inline]
@@ -291,18 +1328,157 @@ impl RuntimeInner {
         self.frames.current_frame()
     }
 
+    /// Reads a local of the current frame. The checked build traps with
+    /// [`TrapKind::BadLocal`] for one the frame doesn't have, and returns None.
+    #[inline]
+    pub fn load_var(&mut self, var: u16) -> Option<i32> {
+        #[cfg(feature = "checked")]
+        if var as usize >= self.frames.current_frame().vars().len() {
+            self.trap(TrapKind::BadLocal);
+            return None;
+        }
+        Some(self.frames.current_frame().load_var(var))
+    }
+
+    /// Writes a local of the current frame. The checked build traps with
+    /// [`TrapKind::BadLocal`] for one the frame doesn't have, the others make room.
     #[inline]
-    pub fn push_frame(&mut self, var_count: u16, arg_count: u16) -> &mut ijvm::Frame {
+    pub fn store_var(&mut self, var: u16, value: i32) {
+        #[cfg(feature = "checked")]
+        if var as usize >= self.frames.current_frame().vars().len() {
+            return self.trap(TrapKind::BadLocal);
+        }
+        self.frames.current_frame().store_var(var, value);
+    }
+
+    /// The header of the method a snapshot's frame runs. Snapshots from before
+    /// frames knew their method only have the call, which names it.
+    fn frame_method(&self, frame: &FrameState) -> InstructionRef {
+        frame
+            .method
+            .unwrap_or_else(|| match self.instructions[frame.restore_pc] {
+                MemoryBlock::RESOLVED_INVOKEVIRTUAL(method) => method,
+                _ => 0,
+            })
+    }
+
+    /// Checks that a snapshot's frames fit the program and a stack of `stack_len`
+    /// values, so running on after restoring them can't reach past a frame's
+    /// locals or below the stack.
+    fn check_frames(
+        &self,
+        frames: &[FrameState],
+        stack_len: usize,
+        main_var_count: u32,
+    ) -> Result<(), SnapshotError> {
+        let Some((main, methods)) = frames.split_first() else {
+            return Err(SnapshotError::Corrupt("missing main frame"));
+        };
+        // locals past the declared ones only exist once stored to, so more are fine
+        if main.vars.len() < main_var_count as usize {
+            return Err(SnapshotError::Corrupt("frame locals"));
+        }
+        for frame in frames {
+            if frame.restore_pc >= self.instructions.len() {
+                return Err(SnapshotError::Corrupt("frame return address"));
+            }
+            if frame.starting_stack_length as usize > stack_len {
+                return Err(SnapshotError::Corrupt("frame stack base"));
+            }
+        }
+        for frame in methods {
+            let Some(MemoryBlock::METHODHEADER { n_args, n_vars }) =
+                self.instructions.get(self.frame_method(frame))
+            else {
+                return Err(SnapshotError::Corrupt("frame method"));
+            };
+            if frame.vars.len() < *n_args as usize + *n_vars as usize {
+                return Err(SnapshotError::Corrupt("frame locals"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks one thread of a snapshot: its pc, stack and frames.
+    fn check_thread(
+        &self,
+        program_counter: InstructionRef,
+        stack: &[i32],
+        frames: &[FrameState],
+        main_var_count: u32,
+    ) -> Result<(), SnapshotError> {
+        // the end of program marker counts, the pc rests on it after EndOfProgram
+        if program_counter >= self.instructions.len() {
+            return Err(SnapshotError::Corrupt("program counter"));
+        }
+        if stack.len() > self.stack.max_size() {
+            return Err(SnapshotError::Corrupt("stack size"));
+        }
+        self.check_frames(frames, stack.len(), main_var_count)?;
+        if frames.len() - 1 > self.frames.max_depth() {
+            return Err(SnapshotError::Corrupt("call depth"));
+        }
+        Ok(())
+    }
+
+    /// The locals main's frame declares, whichever thread is running.
+    fn main_var_count(&self) -> u32 {
+        #[cfg(feature = "green-threads")]
+        if let Some(main) = self.threads.main_context() {
+            return main.frames.main_var_count();
+        }
+        self.frames.main_var_count()
+    }
+
+    fn frames_from(&self, frames: &[FrameState]) -> Vec<ijvm::Frame> {
+        frames
+            .iter()
+            .map(|frame| {
+                ijvm::Frame::with_vars(
+                    frame.starting_stack_length,
+                    frame.vars.clone(),
+                    frame.restore_pc,
+                    self.frame_method(frame),
+                )
+            })
+            .collect()
+    }
+
+    /// Moves OBJREF and the arguments off the stack into a new frame for the
+    /// method with its header at `method`. The frame's base is below OBJREF, which
+    /// is where IRETURN puts the return value. Returns false if that trapped instead.
+    #[inline]
+    pub fn push_frame(&mut self, method: InstructionRef, var_count: u16, arg_count: u16) -> bool {
+        if self.poppable() < arg_count as usize {
+            self.trap(TrapKind::StackUnderflow);
+            return false;
+        }
+        if self.frames.depth() >= self.frames.max_depth() {
+            let backtrace = self.frames.backtrace(BACKTRACE_LEN);
+            self.trap(TrapKind::CallDepthExceeded { backtrace });
+            return false;
+        }
+
+        let restore_pc = self.program_counter() as InstructionRef;
+        let starting_stack_length = self.stack_len() - arg_count as usize;
         self.frames.push_frame(
-            self.stack_len() as u32,
-            var_count as u32,
-            self.program_counter() as InstructionRef,
+            method,
+            starting_stack_length as u32,
+            arg_count as u32 + var_count as u32,
+            restore_pc,
             self.stack.get_ref_top_n(arg_count as usize),
-        )
+        );
+        true
     }
 
+    /// Returns to the caller, or returns false and traps in main.
     #[inline]
-    pub fn pop_frame(&mut self) {
+    pub fn pop_frame(&mut self) -> bool {
+        if self.frames.depth() == 0 {
+            self.trap(TrapKind::ReturnFromMain);
+            return false;
+        }
+
         let frame = self.frames.pop_frame();
 
         let restore_pc = frame.restore_pc();
@@ -310,95 +1486,445 @@ impl RuntimeInner {
 
         self.set_pc(restore_pc);
         self.pop_until_size(stack_len);
+        true
+    }
+
+    #[inline]
+    pub fn observer(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Calls a native method. The arguments and OBJREF leave the stack the same
+    /// way they do for an IJVM method, and the return value takes their place.
+    /// One that isn't registered, or is already running, traps with
+    /// [`TrapKind::BadMethod`]. Returns the return value, or None if execution
+    /// stopped instead.
+    pub(crate) fn call_native(&mut self, native: usize, n_args: u16) -> Option<i32> {
+        // taken out while it runs, so it can borrow the runtime
+        let Some(mut function) = self
+            .natives
+            .get_mut(native)
+            .and_then(|native| native.function.take())
+        else {
+            self.trap(TrapKind::BadMethod);
+            return None;
+        };
+        let n_args = n_args as usize;
+        if self.poppable() < n_args {
+            self.natives[native].function = Some(function);
+            self.trap(TrapKind::StackUnderflow);
+            return None;
+        }
+        let base = self.stack_len() - n_args;
+        // without OBJREF
+        let args = self.stack.stack_slice()[base..]
+            .iter()
+            .skip(1)
+            .copied()
+            .collect::<Vec<_>>();
+        self.pop_until_size(base);
+
+        let value = function(self, &args);
+        self.natives[native].function = Some(function);
+
+        // one that trapped returns nothing
+        if matches!(self.outcome, Outcome::Trapped(_)) {
+            return None;
+        }
+        self.stack_push(value);
+        (!self.is_finished).then_some(value)
     }
 }
 
-pub fn init_ijvm(binary_file: &str) -> Runtime {
-    let mut fp = std::fs::File::open(binary_file).unwrap();
-    let mut header = [0u8; 4];
-    fp.read_exact(&mut header).unwrap();
-    let header = u32::from_be_bytes(header);
-    if header != 0x1DEADFAD {
-        panic!("Invalid header");
-    }
-    let constants = load_constants(ijvm::IJVMBlock::read_block(
-        fp.by_ref().bytes().map(|x| x.unwrap()),
-    ));
-    let text = ijvm::IJVMBlock::read_block(fp.by_ref().bytes().map(|x| x.unwrap()));
-
-    // dbg!(&constants, &text.contents);
-
-    // classify constants
-    // find 0x13 in text.contents, the index of next constant is stackvalue
-    let mut constants_kinded = constants
+#[cfg(feature = "green-threads")]
+impl<R: Read, W: Write, O: Observer> RuntimeInner<R, W, O> {
+    #[inline]
+    pub fn thread_id(&self) -> ThreadId {
+        self.threads.current()
+    }
+
+    /// Executes `SPAWN`: moves OBJREF and the arguments off the stack into a new
+    /// thread that runs the method at `method`, and pushes the thread's id.
+    pub fn spawn_thread(&mut self, method: InstructionRef) {
+        let MemoryBlock::METHODHEADER { n_args, n_vars } = self.instructions[method] else {
+            return self.trap(TrapKind::BadMethod);
+        };
+        if self.poppable() < n_args as usize {
+            return self.trap(TrapKind::StackUnderflow);
+        }
+
+        let mut frames = self.thread_frames(0);
+        // returning from the method lands on the end of program marker, which ends the thread
+        let restore_pc = self.instructions.len() - 2;
+        frames.push_frame(
+            method,
+            0,
+            n_args as u32 + n_vars as u32,
+            restore_pc,
+            self.stack.get_ref_top_n(n_args as usize),
+        );
+        let thread = self.threads.spawn(Context {
+            stack: self.thread_stack(),
+            frames,
+            program_counter: method,
+        });
+
+        if O::ENABLED {
+            self.observer.on_spawn(thread, method);
+        }
+        self.stack_push(thread as i32);
+    }
+
+    /// Executes `YIELD`.
+    pub fn yield_thread(&mut self) {
+        self.switch_thread();
+    }
+
+    /// Executes `JOIN`: pops a thread id and pushes what the thread returned. If
+    /// it's still running, the current thread waits, and executes `JOIN` again
+    /// once it's done.
+    pub fn join_thread(&mut self) {
+        let Some(thread) = self.stack_pop() else {
+            return;
+        };
+        let Some(result) = usize::try_from(thread)
+            .ok()
+            .and_then(|thread| self.threads.result(thread))
+        else {
+            return self.trap(TrapKind::BadThread);
+        };
+        if let Some(value) = result {
+            return self.stack_push(value);
+        }
+
+        let thread = thread as ThreadId;
+        if thread == self.threads.current() || self.threads.pinned {
+            return self.trap(TrapKind::Deadlock);
+        }
+        self.threads.join(thread);
+        if self.threads.next_runnable().is_none() {
+            return self.trap(TrapKind::Deadlock);
+        }
+        self.stack_push(thread as i32);
+        self.program_counter = self.program_counter.wrapping_sub(1);
+        self.switch_thread();
+    }
+
+    /// Hands the processor to the next thread in turn that can run. Traps with
+    /// [`TrapKind::Deadlock`] if there is none.
+    fn switch_thread(&mut self) {
+        let from = self.threads.current();
+        let next = match self.threads.next_runnable() {
+            _ if self.threads.pinned => from,
+            Some(next) => next,
+            None => return self.trap(TrapKind::Deadlock),
+        };
+        if next == from {
+            return self.threads.restart_slice();
+        }
+
+        let mut context = self.threads.switch(next);
+        core::mem::swap(&mut self.stack, &mut context.stack);
+        core::mem::swap(&mut self.frames, &mut context.frames);
+        core::mem::swap(&mut self.program_counter, &mut context.program_counter);
+        self.threads.store(from, context);
+
+        if O::ENABLED {
+            self.observer.on_thread_switch(from, next);
+        }
+    }
+
+    /// Ends every thread but main, and makes main's state current.
+    fn end_threads(&mut self) {
+        if let Some(main) = self.threads.end_all() {
+            self.stack = main.stack;
+            self.frames = main.frames;
+            self.program_counter = main.program_counter;
+        }
+    }
+
+    /// Checks that a snapshot's threads fit together and the program. Only the
+    /// threads that aren't running or finished have a state of their own.
+    fn check_threads(
+        &self,
+        threads: &ThreadsState,
+        main_var_count: u32,
+    ) -> Result<(), SnapshotError> {
+        let count = threads.threads.len();
+        if threads.current >= count
+            || matches!(threads.threads[0].status, ThreadStatus::Finished(_))
+        {
+            return Err(SnapshotError::Corrupt("threads"));
+        }
+        for (id, thread) in threads.threads.iter().enumerate() {
+            if matches!(thread.status, ThreadStatus::Joining(other) if other >= count) {
+                return Err(SnapshotError::Corrupt("threads"));
+            }
+            let has_context =
+                id != threads.current && !matches!(thread.status, ThreadStatus::Finished(_));
+            match &thread.context {
+                Some(context) if has_context => self.check_thread(
+                    context.program_counter,
+                    &context.stack,
+                    &context.frames,
+                    if id == 0 { main_var_count } else { 0 },
+                )?,
+                None if !has_context => {}
+                _ => return Err(SnapshotError::Corrupt("threads")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets up the threads of a checked snapshot, with main's state current. The
+    /// caller restores the running thread's state into the runtime afterwards.
+    fn restore_threads(&mut self, threads: &ThreadsState, main_var_count: u32) {
+        if threads.current != 0 {
+            self.stack = self.thread_stack();
+            self.frames = self.thread_frames(0);
+        }
+        let restored = threads
+            .threads
+            .iter()
+            .enumerate()
+            .map(|(id, thread)| {
+                let context = thread.context.as_ref().map(|state| {
+                    let mut stack = self.thread_stack();
+                    stack.restore(&state.stack, state.top_value);
+                    let mut frames =
+                        self.thread_frames(if id == 0 { main_var_count } else { 0 });
+                    frames.replace(self.frames_from(&state.frames));
+                    Context {
+                        stack,
+                        frames,
+                        program_counter: state.program_counter,
+                    }
+                });
+                (thread.status, context)
+            })
+            .collect();
+        self.threads.restore(threads.current, threads.remaining, restored);
+    }
+
+    fn thread_stack(&self) -> Stack {
+        Stack::with_size(StackSize::growable(THREAD_STACK_SIZE, self.stack.max_size()))
+    }
+
+    fn thread_frames(&self, main_var_count: u32) -> FrameStack {
+        let mut frames = FrameStack::new(main_var_count);
+        frames.set_max_depth(self.frames.max_depth());
+        frames
+    }
+}
+
+fn frame_states(frames: &FrameStack) -> Vec<FrameState> {
+    frames
         .iter()
-        .map(|x| ConstantKind::None(*x))
-        .collect::<Vec<_>>();
+        .map(|frame| FrameState {
+            starting_stack_length: frame.starting_stack_length(),
+            restore_pc: frame.restore_pc(),
+            method: Some(frame.method()),
+            vars: frame.vars().to_vec(),
+        })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+pub fn init_ijvm(binary_file: &str) -> Runtime {
+    Program::load(binary_file).runtime()
+}
+
+/// A parsed IJVM binary. Loading it once and making a runtime per run saves
+/// reading and parsing the file every time.
+#[derive(Debug, Clone)]
+pub struct Program {
+    instructions: Vec<MemoryBlock>,
+    constants: Vec<Constant>,
+    text: Vec<u8>,
+    // instruction of every text byte
+    mappings: Vec<InstructionRef>,
+    fingerprint: u64,
+    main_var_count: u32,
+    stack_size: StackSize,
+}
 
-    for ind in 0..text.contents.len() {
-        let byte = &text.contents[ind];
-        if *byte == 0x13 {
+impl Program {
+    #[cfg(feature = "std")]
+    pub fn load(binary_file: &str) -> Program {
+        Program::try_load(binary_file).unwrap()
+    }
+
+    /// Loads like [`Program::load`], but fails instead of panicking when the file
+    /// can't be read or doesn't start like an IJVM binary.
+    #[cfg(feature = "std")]
+    pub fn try_load(binary_file: &str) -> io::Result<Program> {
+        Program::from_bytes(&std::fs::read(binary_file)?)
+    }
+
+    /// Parses a binary already in memory, failing if it doesn't start like an
+    /// IJVM binary, is cut short or holds text that doesn't decode.
+    pub fn from_bytes(binary: &[u8]) -> io::Result<Program> {
+        let Some((header, rest)) = binary.split_first_chunk::<4>() else {
+            return Err(ErrorKind::UnexpectedEof.into());
+        };
+        if u32::from_be_bytes(*header) != 0x1DEADFAD {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        let mut fp = rest.iter().copied();
+        let pool = ijvm::IJVMBlock::read_block(fp.by_ref());
+        let text = ijvm::IJVMBlock::read_block(fp.by_ref());
+        if pool.contents.len() != pool.pool_size as usize
+            || text.contents.len() != text.pool_size as usize
+        {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let constants = load_constants(pool);
+
+        // dbg!(&constants, &text.contents);
+
+        // classify constants
+        // find 0x13 in text.contents, the index of next constant is stackvalue
+        let mut constants_kinded = constants
+            .iter()
+            .map(|x| ConstantKind::None(*x))
+            .collect::<Vec<_>>();
+
+        for ind in 0..text.contents.len() {
+            let byte = &text.contents[ind];
+            if *byte == 0x13 {
+                if text.contents.len() < ind + 3 {
+                    continue;
+                }
+                let constant_ind =
+                    (text.contents[ind + 1] as usize) << 8 | text.contents[ind + 2] as usize;
+
+                if constants_kinded.len() > constant_ind {
+                    constants_kinded[constant_ind] = constants_kinded[constant_ind].clone().as_stack();
+                }
+            }
+        }
+
+        // do the same for methods with 0xB6
+        for ind in 0..text.contents.len() {
+            let byte = &text.contents[ind];
             if text.contents.len() < ind + 3 {
                 continue;
             }
-            let constant_ind =
-                (text.contents[ind + 1] as usize) << 8 | text.contents[ind + 2] as usize;
-
-            if constants_kinded.len() > constant_ind {
-                constants_kinded[constant_ind] = constants_kinded[constant_ind].clone().as_stack();
+            // SPAWN names its method like INVOKEVIRTUAL
+            if *byte == 0xB6 || (cfg!(feature = "green-threads") && *byte == 0xF0) {
+                let constant_ind =
+                    (text.contents[ind + 1] as usize) << 8 | text.contents[ind + 2] as usize;
+                // the byte may just be an operand, which can point anywhere
+                if let Some(constant) = constants_kinded.get_mut(constant_ind) {
+                    *constant = constant.clone().as_method();
+                }
             }
         }
+
+        let fingerprint = snapshot::fingerprint(&constants, &text.contents);
+        let (mut instructions, mappings) =
+            IJVMParser::parse_with_mappings(text.contents.iter().cloned(), constants_kinded)
+                .map_err(|_| io::Error::from(ErrorKind::InvalidData))?;
+        // running past the text executes this, instead of whatever follows in memory
+        instructions.push(MemoryBlock::END);
+
+        // main's local count isn't stored anywhere, so make room for every index the program uses
+        let main_var_count = instructions
+            .iter()
+            .filter_map(|block| match block {
+                MemoryBlock::ILOAD(var) | MemoryBlock::ISTORE(var) | MemoryBlock::IINC(var, _) => {
+                    Some(*var as u32 + 1)
+                }
+                MemoryBlock::WIDE(
+                    WideMemoryBlock::ILOAD(var)
+                    | WideMemoryBlock::ISTORE(var)
+                    | WideMemoryBlock::IIINC(var, _),
+                ) => Some(*var as u32 + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        // println!(
+        //     "Loaded ijvm file {}, constants pool size: {}, text pool size: {}",
+        //     binary_file,
+        //     constants.len(),
+        //     text.pool_size
+        // );
+        Ok(Program {
+            instructions,
+            constants,
+            text: text.contents,
+            mappings,
+            fingerprint,
+            main_var_count,
+            stack_size: StackSize::default(),
+        })
     }
 
-    // do the same for methods with 0xB6
-    for ind in 0..text.contents.len() {
-        let byte = &text.contents[ind];
-        if text.contents.len() < ind + 3 {
-            continue;
-        }
-        if *byte == 0xB6 {
-            let constant_ind =
-                (text.contents[ind + 1] as usize) << 8 | text.contents[ind + 2] as usize;
-            constants_kinded[constant_ind] = constants_kinded[constant_ind].clone().as_method();
-        }
+    /// Sets the operand stack size of the runtimes made from now on.
+    pub fn set_stack_size(&mut self, size: StackSize) {
+        self.stack_size = size;
     }
 
-    // check none constant is none
-    for (i, x) in constants_kinded.iter().enumerate() {
-        if x.is_none() {
-            println!("WARNING: Constant {} is none", i);
-        }
+    /// The program's instructions, without the end of program marker.
+    pub fn instructions(&self) -> &[MemoryBlock] {
+        &self.instructions[..self.instructions.len() - 1]
     }
 
-    let instructions = IJVMParser::parse_iter(text.contents.iter().cloned(), constants_kinded);
+    pub fn constants(&self) -> &[Constant] {
+        &self.constants
+    }
 
-    // println!(
-    //     "Loaded ijvm file {}, constants pool size: {}, text pool size: {}",
-    //     binary_file,
-    //     constants.len(),
-    //     text.pool_size
-    // );
-    // let current_frame = ijvm::Frame::new(0, 0, 0);
-    let program_counter = 0;
-    let is_finished = false;
-    let stack = Stack::new();
-    let out_stream = std::io::stderr();
-    let in_stream = std::io::stdin();
-    let inner = RuntimeInner {
-        instructions: instructions.clone(),
-        constants,
-        frames: FrameStack::new(),
-        program_counter,
-        is_finished,
-        stack,
-        out_stream,
-        in_stream,
-        #[cfg(feature = "metrics")]
-        metrics: Metrics::default(),
-    };
-    Runtime {
-        inner,
-        instructions,
+    /// The text block as it is in the binary.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// A fresh machine for running the program, reading from stdin and writing to
+    /// stderr. Without std it has no input, and its output collects in
+    /// `inner.out_stream`.
+    pub fn runtime(&self) -> Runtime {
+        // let current_frame = ijvm::Frame::new(0, 0, 0);
+        let program_counter = 0;
+        let is_finished = false;
+        let stack = Stack::with_size(self.stack_size);
+        #[cfg(feature = "std")]
+        let (out_stream, in_stream) = (std::io::stderr(), std::io::stdin());
+        #[cfg(not(feature = "std"))]
+        let (out_stream, in_stream) = (Vec::new(), &[][..]);
+        let inner = RuntimeInner {
+            instructions: self.instructions.clone(),
+            constants: self.constants.clone(),
+            program: self.fingerprint,
+            frames: FrameStack::new(self.main_var_count),
+            program_counter,
+            is_finished,
+            outcome: Outcome::Running,
+            stack,
+            out_stream,
+            in_stream,
+            input: InputState::default(),
+            overflow: OverflowMode::default(),
+            #[cfg(target_has_atomic = "ptr")]
+            interrupt: InterruptHandle::new(),
+            observer: NoopObserver,
+            natives: Vec::new(),
+            #[cfg(feature = "green-threads")]
+            threads: Threads::new(),
+            #[cfg(feature = "metrics")]
+            metrics: Metrics::default(),
+        };
+        Runtime {
+            inner,
+            instructions: self.instructions.clone(),
+            mappings: self.mappings.clone(),
+            symbols: Vec::new(),
+            breakpoints: Vec::new(),
+            breakpoint_taken: false,
+            costs: CostTable::default(),
+            fuel_used: 0,
+        }
     }
 }
 
@@ -1,7 +1,12 @@
 
-#[allow(unused_imports)]
-use std::{io::{Read, Write}, iter::Peekable};
-use crate::ijvm_core::{ConstantKind, InstructionRef, RuntimeInner};
+use alloc::vec::Vec;
+use core::{fmt, iter::Peekable};
+use crate::{
+    ijvm_core::{ConstantKind, InstructionRef, RuntimeInner},
+    io::{Read, Write},
+    observer::Observer,
+    trap::TrapKind,
+};
 
 #[derive(Clone, PartialEq, Debug)]
 #[allow(non_camel_case_types)]
@@ -12,7 +17,7 @@ pub enum MemoryBlock {
     HALT,
     IADD,
     IAND,
-    IINC(u8, u8),
+    IINC(u8, i8),
     ILOAD(u8),
     IN,
     IOR,
@@ -25,7 +30,33 @@ pub enum MemoryBlock {
     SWAP,
     WIDE(WideMemoryBlock),
 
+    #[cfg(feature = "ext-arith")]
+    IMUL,
+    #[cfg(feature = "ext-arith")]
+    IDIV,
+    #[cfg(feature = "ext-arith")]
+    IREM,
+    #[cfg(feature = "ext-arith")]
+    ISHL,
+    #[cfg(feature = "ext-arith")]
+    ISHR,
+    #[cfg(feature = "ext-arith")]
+    IUSHR,
+    #[cfg(feature = "ext-arith")]
+    IXOR,
+    #[cfg(feature = "ext-arith")]
+    INEG,
+
+    #[cfg(feature = "green-threads")]
+    RESOLVED_SPAWN(InstructionRef),
+    #[cfg(feature = "green-threads")]
+    YIELD,
+    #[cfg(feature = "green-threads")]
+    JOIN,
+
     METHODHEADER { n_args: u16, n_vars: u16 },
+    // replaces the header of a method implemented by the host
+    NATIVE { native: usize, n_args: u16 },
     RESOLVED_INVOKEVIRTUAL(InstructionRef),
     RESOLVED_GOTO(InstructionRef),
     RESOLVED_IFEQ(InstructionRef),
@@ -33,6 +64,13 @@ pub enum MemoryBlock {
     RESOLVED_IF_ICMPEQ(InstructionRef),
     RESOLVED_LDC_W(i32),
     Delayed(ResolveLater),
+    // past the last instruction
+    END,
+    // stands in for an instruction with a breakpoint set
+    BREAKPOINT,
+    // bytes that don't decode, with their first byte
+    #[cfg(feature = "checked")]
+    INVALID(u8),
     // WIDE(),
     // NEWARRAY(),
     // IALOAD(),
@@ -49,7 +87,7 @@ pub enum MemoryBlock {
 pub enum WideMemoryBlock {
     ILOAD(u16),
     ISTORE(u16),
-    IIINC(u8, u16),
+    IIINC(u16, i8),
 }
 
 #[derive(Clone, Debug)]
@@ -60,6 +98,8 @@ where
     _data: Peekable<I>,
     bytes_read: u64,
     total_bytes_read: u64,
+    // an instruction ran past the end of the text
+    truncated: bool,
 }
 impl<I> IJVMIter<I>
 where
@@ -80,7 +120,13 @@ where
     }
 
     fn get_byte(&mut self) -> u8 {
-        self.next().unwrap()
+        match self.next() {
+            Some(byte) => byte,
+            None => {
+                self.truncated = true;
+                0
+            }
+        }
     }
 
     fn get_byte_pair(&mut self) -> (u8, u8) {
@@ -121,8 +167,46 @@ pub enum ResolveLater {
     IFEQ(i16),
     IFLT(i16),
     IF_ICMPEQ(i16),
+    #[cfg(feature = "green-threads")]
+    SPAWN(u16),
 }
 
+/// Why the text didn't parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The text ends in the middle of the instruction with this opcode.
+    Truncated(u8),
+    /// WIDE is followed by a byte that can't be widened.
+    BadWide(u8),
+    /// LDC_W of a constant past the end of the pool.
+    MissingConstant(u16),
+}
+
+impl ParseError {
+    /// The opcode of the instruction that didn't parse.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            ParseError::Truncated(opcode) => *opcode,
+            ParseError::BadWide(_) => 0xC4,
+            ParseError::MissingConstant(_) => 0x13,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Truncated(opcode) => {
+                write!(f, "text ends inside instruction 0x{:02X}", opcode)
+            }
+            ParseError::BadWide(byte) => write!(f, "invalid instruction after WIDE: {}", byte),
+            ParseError::MissingConstant(ind) => write!(f, "LDC_W of missing constant {}", ind),
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
 pub struct IJVMParser<I>
 where
     I: Iterator<Item = u8>,
@@ -137,18 +221,34 @@ impl<I> IJVMParser<I>
 where
     I: Iterator<Item = u8>,
 {
-    fn get_target(&self, current: InstructionRef, offset: i16) -> InstructionRef {
+    /// Resolves a branch offset, relative to the first byte of the branching instruction.
+    /// Targets outside the text or inside another instruction don't resolve.
+    fn get_target(&self, current: InstructionRef, offset: i16) -> Option<InstructionRef> {
         // find value of current within mappings
-        let mut ind = 0;
-        while self.mappings[ind] != current {
-            ind += 1;
+        let ind = self.mappings.iter().position(|m| *m == current)?;
+
+        let ind = ind.checked_add_signed(offset as isize)?;
+        let block = *self.mappings.get(ind)?;
+        if ind > 0 && self.mappings[ind - 1] == block {
+            return None;
         }
+        // the pc gets incremented after the jump, this wraps for a target of 0
+        Some(block.wrapping_sub(1) as InstructionRef)
+    }
 
-        ind = ind.wrapping_add_signed(offset as isize);
-        (self.mappings[ind] - 1) as InstructionRef
+    pub fn parse_iter(
+        iterator: I,
+        constants: Vec<ConstantKind>,
+    ) -> Result<Vec<MemoryBlock>, ParseError> {
+        Ok(Self::parse_with_mappings(iterator, constants)?.0)
     }
 
-    pub fn parse_iter(iterator: I, constants: Vec<ConstantKind>) -> Vec<MemoryBlock> {
+    /// Like [`IJVMParser::parse_iter`], also returning which instruction each byte
+    /// of the text belongs to.
+    pub fn parse_with_mappings(
+        iterator: I,
+        constants: Vec<ConstantKind>,
+    ) -> Result<(Vec<MemoryBlock>, Vec<InstructionRef>), ParseError> {
         let mut parser = IJVMParser {
             blocks: Vec::new(),
             mappings: Vec::new(),
@@ -156,11 +256,12 @@ where
                 _data: iterator.peekable(),
                 bytes_read: 0,
                 total_bytes_read: 0,
+                truncated: false,
             },
             constants,
         };
         while !parser.data.is_end() {
-            let block = parser.parse_memory_block();
+            let block = parser.parse_memory_block()?;
 
             parser.blocks.push(block);
 
@@ -172,56 +273,82 @@ where
         // resolve delayed instructions
         for i in 0..parser.blocks.len() {
             if let MemoryBlock::Delayed(instruction) = &parser.blocks[i] {
-                parser.blocks[i] = match instruction {
-                    ResolveLater::GOTO(offset) => {
-                        MemoryBlock::RESOLVED_GOTO(parser.get_target(i as InstructionRef, *offset))
-                    }
-                    ResolveLater::INVOKEVIRTUAL(offset) => {
-                        let constant_val = parser.constants[*offset as usize].unwrap_method_ref();
-                        let mapped = parser.mappings[constant_val as usize];
-                        MemoryBlock::RESOLVED_INVOKEVIRTUAL(mapped as InstructionRef)
-                    }
-                    ResolveLater::IFEQ(offset) => {
-                        MemoryBlock::RESOLVED_IFEQ(parser.get_target(i as InstructionRef, *offset))
-                    }
-                    ResolveLater::IFLT(offset) => {
-                        MemoryBlock::RESOLVED_IFLT(parser.get_target(i as InstructionRef, *offset))
-                    }
-                    ResolveLater::IF_ICMPEQ(offset) => MemoryBlock::RESOLVED_IF_ICMPEQ(
-                        parser.get_target(i as InstructionRef, *offset),
-                    ),
+                let resolved = match instruction {
+                    ResolveLater::GOTO(offset) => parser
+                        .get_target(i as InstructionRef, *offset)
+                        .map(MemoryBlock::RESOLVED_GOTO),
+                    ResolveLater::INVOKEVIRTUAL(offset) => parser
+                        .constants
+                        .get(*offset as usize)
+                        .and_then(|c| parser.mappings.get(c.unchecked_value() as usize))
+                        .map(|mapped| MemoryBlock::RESOLVED_INVOKEVIRTUAL(*mapped as InstructionRef)),
+                    ResolveLater::IFEQ(offset) => parser
+                        .get_target(i as InstructionRef, *offset)
+                        .map(MemoryBlock::RESOLVED_IFEQ),
+                    ResolveLater::IFLT(offset) => parser
+                        .get_target(i as InstructionRef, *offset)
+                        .map(MemoryBlock::RESOLVED_IFLT),
+                    ResolveLater::IF_ICMPEQ(offset) => parser
+                        .get_target(i as InstructionRef, *offset)
+                        .map(MemoryBlock::RESOLVED_IF_ICMPEQ),
+                    #[cfg(feature = "green-threads")]
+                    ResolveLater::SPAWN(offset) => parser
+                        .constants
+                        .get(*offset as usize)
+                        .and_then(|c| parser.mappings.get(c.unchecked_value() as usize))
+                        .map(|mapped| MemoryBlock::RESOLVED_SPAWN(*mapped as InstructionRef)),
                 };
+                // a target that can't be resolved stays delayed and traps when executed
+                if let Some(resolved) = resolved {
+                    parser.blocks[i] = resolved;
+                }
                 // dbg!(&parser.blocks[i]);
             }
         }
 
-        parser.blocks
+        Ok((parser.blocks, parser.mappings))
     }
 
-    fn parse_wide(&mut self) -> WideMemoryBlock {
-        match self.data.next().unwrap() {
+    fn parse_wide(&mut self) -> Result<MemoryBlock, ParseError> {
+        Ok(MemoryBlock::WIDE(match self.data.get_byte() {
             0x15 => WideMemoryBlock::ILOAD(self.data.get_ushort()),
             0x36 => WideMemoryBlock::ISTORE(self.data.get_ushort()),
-            0x84 => WideMemoryBlock::IIINC(self.data.get_byte(), self.data.get_ushort()),
-            c => panic!("Invalid instruction after WIDE: {}", c),
+            0x84 => WideMemoryBlock::IIINC(self.data.get_ushort(), self.data.get_byte() as i8),
+            c => return Err(ParseError::BadWide(c)),
+        }))
+    }
+
+    /// Parses the next instruction. Bytes that don't decode are an error, except
+    /// in the checked build, which parses them into an instruction that traps.
+    pub fn parse_memory_block(&mut self) -> Result<MemoryBlock, ParseError> {
+        let mut block = self.parse_block();
+        if self.data.truncated {
+            self.data.truncated = false;
+            let opcode = block
+                .as_ref()
+                .map_or_else(ParseError::opcode, MemoryBlock::opcode);
+            block = Err(ParseError::Truncated(opcode));
         }
+        #[cfg(feature = "checked")]
+        let block = Ok(block.unwrap_or_else(|error| MemoryBlock::INVALID(error.opcode())));
+        block
     }
 
-    // instructions parsed in first pass
-    pub fn parse_memory_block(&mut self) -> MemoryBlock {
+    fn parse_block(&mut self) -> Result<MemoryBlock, ParseError> {
         // check if this is a method ref, from constants
         let ind = self.data.total_bytes_read();
         if self
             .constants
-            .contains(&ConstantKind::MethodRef(ind as i32))
+            .iter()
+            .any(|c| matches!(c, ConstantKind::MethodRef(x) | ConstantKind::Either(x) if *x == ind as i32))
         {
-            return MemoryBlock::METHODHEADER {
+            return Ok(MemoryBlock::METHODHEADER {
                 n_args: self.data.get_ushort(),
                 n_vars: self.data.get_ushort(),
-            };
+            });
         }
 
-        match self.data.next().unwrap() {
+        Ok(match self.data.get_byte() {
             0x10 => MemoryBlock::BIPUSH(self.data.get_byte() as i8),
             0x59 => MemoryBlock::DUP,
             0xFE => MemoryBlock::ERR,
@@ -230,7 +357,7 @@ where
             0x7E => MemoryBlock::IAND,
             0x84 => {
                 let pair = self.data.get_byte_pair();
-                MemoryBlock::IINC(pair.0, pair.1)
+                MemoryBlock::IINC(pair.0, pair.1 as i8)
             }
             0x15 => MemoryBlock::ILOAD(self.data.get_byte()),
             0xFC => MemoryBlock::IN,
@@ -238,14 +365,43 @@ where
             0xAC => MemoryBlock::IRETURN,
             0x36 => MemoryBlock::ISTORE(self.data.get_byte()),
             0x64 => MemoryBlock::ISUB,
-            0x13 => MemoryBlock::RESOLVED_LDC_W(
-                self.constants[self.data.get_short() as usize].unwrap_stack_value(),
-            ),
+            0x13 => {
+                let ind = self.data.get_ushort();
+                match self.constants.get(ind as usize) {
+                    // the constant is a stack value unless the LDC_W is cut off
+                    Some(constant) => MemoryBlock::RESOLVED_LDC_W(constant.unchecked_value()),
+                    None => return Err(ParseError::MissingConstant(ind)),
+                }
+            }
             0x00 => MemoryBlock::NOP,
             0xFD => MemoryBlock::OUT,
             0x57 => MemoryBlock::POP,
             0x5F => MemoryBlock::SWAP,
-            0xC4 => MemoryBlock::WIDE(self.parse_wide()),
+            0xC4 => self.parse_wide()?,
+
+            #[cfg(feature = "ext-arith")]
+            0x68 => MemoryBlock::IMUL,
+            #[cfg(feature = "ext-arith")]
+            0x6C => MemoryBlock::IDIV,
+            #[cfg(feature = "ext-arith")]
+            0x70 => MemoryBlock::IREM,
+            #[cfg(feature = "ext-arith")]
+            0x74 => MemoryBlock::INEG,
+            #[cfg(feature = "ext-arith")]
+            0x78 => MemoryBlock::ISHL,
+            #[cfg(feature = "ext-arith")]
+            0x7A => MemoryBlock::ISHR,
+            #[cfg(feature = "ext-arith")]
+            0x7C => MemoryBlock::IUSHR,
+            #[cfg(feature = "ext-arith")]
+            0x82 => MemoryBlock::IXOR,
+
+            #[cfg(feature = "green-threads")]
+            0xF0 => MemoryBlock::Delayed(ResolveLater::SPAWN(self.data.get_ushort())),
+            #[cfg(feature = "green-threads")]
+            0xF1 => MemoryBlock::YIELD,
+            #[cfg(feature = "green-threads")]
+            0xF2 => MemoryBlock::JOIN,
 
             // resolve later
             0x99 => MemoryBlock::Delayed(ResolveLater::IFEQ(self.data.get_short())),
@@ -278,50 +434,140 @@ where
                     n_vars: self.data.get_ushort(),
                 }
             }
-        }
+        })
     }
 }
 
 impl MemoryBlock {
-    #[inline]
-    pub fn execute(&self, runtime: &mut RuntimeInner) {
+    // the run loop is only fast with the match inlined into it, which the cold
+    // arms stopped happening on its own
+    #[inline(always)]
+    pub fn execute<R: Read, W: Write, O: Observer>(
+        &self,
+        runtime: &mut RuntimeInner<R, W, O>,
+    ) {
         match &self {
             MemoryBlock::IADD => {
-                let top = runtime.stack_pop();
-                let second_top = runtime.stack_pop();
-                runtime.stack_push(top.wrapping_add(second_top));
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                let Some(result) = runtime.add(second_top, top) else {
+                    return;
+                };
+                runtime.stack_push(result);
             }
             MemoryBlock::ISUB => {
-                let top = runtime.stack_pop();
-                let second_top = runtime.stack_pop();
-                runtime.stack_push(second_top - top);
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                let Some(result) = runtime.sub(second_top, top) else {
+                    return;
+                };
+                runtime.stack_push(result);
             }
             MemoryBlock::IAND => {
-                let top = runtime.stack_pop();
-                let second_top = runtime.stack_pop();
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
                 runtime.stack_push(top & second_top);
             }
             MemoryBlock::IOR => {
-                let top = runtime.stack_pop();
-                let second_top = runtime.stack_pop();
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
                 runtime.stack_push(top | second_top);
             }
+            // operands are popped like ISUB: the top of stack is the right hand side
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IMUL => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                let Some(result) = runtime.mul(second_top, top) else {
+                    return;
+                };
+                runtime.stack_push(result);
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IDIV => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                if top == 0 {
+                    return runtime.trap(TrapKind::DivisionByZero);
+                }
+                let Some(result) = runtime.div(second_top, top) else {
+                    return;
+                };
+                runtime.stack_push(result);
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IREM => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                if top == 0 {
+                    return runtime.trap(TrapKind::DivisionByZero);
+                }
+                // fits in every mode, MIN % -1 is 0
+                runtime.stack_push(second_top.wrapping_rem(top));
+            }
+            // shift distances only use their low 5 bits, as in the JVM
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHL => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                runtime.stack_push(second_top.wrapping_shl(top as u32));
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHR => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                runtime.stack_push(second_top.wrapping_shr(top as u32));
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IUSHR => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                runtime.stack_push((second_top as u32).wrapping_shr(top as u32) as i32);
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IXOR => {
+                let Some((top, second_top)) = runtime.stack_pop2() else {
+                    return;
+                };
+                runtime.stack_push(top ^ second_top);
+            }
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::INEG => {
+                let Some(top) = runtime.stack_pop() else {
+                    return;
+                };
+                let Some(result) = runtime.neg(top) else {
+                    return;
+                };
+                runtime.stack_push(result);
+            }
+            // SPAWN takes its arguments like INVOKEVIRTUAL, and pushes the new thread's id
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::RESOLVED_SPAWN(ind) => runtime.spawn_thread(*ind),
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::YIELD => runtime.yield_thread(),
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::JOIN => runtime.join_thread(),
             MemoryBlock::BIPUSH(val) => {
                 runtime.stack_push(*val as i32);
             }
             MemoryBlock::OUT => {
-                let _popped = runtime.stack_pop();
-                // runtime.out_stream().write_all(&[popped as u8]).unwrap();
-            }
-            MemoryBlock::IN => {
-                let mut loaded = [0u8; 1];
-                let n_loaded = runtime.in_stream().read(&mut loaded).unwrap();
-                if n_loaded > 0 {
-                    runtime.stack_push(loaded[0] as i32);
-                } else {
-                    runtime.stack_push(0);
-                }
+                let Some(popped) = runtime.stack_pop() else {
+                    return;
+                };
+                runtime.write_output(popped as u8);
             }
+            MemoryBlock::IN => runtime.read_input(),
             MemoryBlock::SWAP => runtime.stack_swap(),
             MemoryBlock::POP => {
                 runtime.stack_pop();
@@ -330,61 +576,82 @@ impl MemoryBlock {
                 runtime.halt();
             }
             MemoryBlock::DUP => {
-                let top = runtime.stack_pop();
+                let Some(top) = runtime.stack_pop() else {
+                    return;
+                };
                 runtime.stack_push(top);
                 runtime.stack_push(top);
             }
-            MemoryBlock::RESOLVED_GOTO(pc) => runtime.set_pc(*pc),
+            MemoryBlock::RESOLVED_GOTO(pc) => runtime.jump(*pc),
             MemoryBlock::RESOLVED_IFEQ(pc) => {
-                let top = runtime.stack_pop();
+                let Some(top) = runtime.stack_pop() else {
+                    return;
+                };
                 if top == 0 {
-                    runtime.set_pc(*pc)
+                    runtime.jump(*pc)
                 }
             }
             MemoryBlock::RESOLVED_IFLT(pc) => {
-                let top = runtime.stack_pop();
+                let Some(top) = runtime.stack_pop() else {
+                    return;
+                };
                 if top < 0 {
-                    runtime.set_pc(*pc)
+                    runtime.jump(*pc)
                 }
             }
             MemoryBlock::RESOLVED_IF_ICMPEQ(pc) => {
-                let top = runtime.stack_pop();
-                let top2 = runtime.stack_pop();
+                let Some((top, top2)) = runtime.stack_pop2() else {
+                    return;
+                };
                 if top == top2 {
-                    runtime.set_pc(*pc)
+                    runtime.jump(*pc)
                 }
             }
             MemoryBlock::RESOLVED_LDC_W(constant) => {
                 runtime.stack_push(*constant);
             }
             MemoryBlock::ILOAD(ident) => {
-                let value = runtime.frame().load_var(*ident as u16);
+                let Some(value) = runtime.load_var(*ident as u16) else {
+                    return;
+                };
                 runtime.stack_push(value);
             }
             MemoryBlock::ISTORE(ident) => {
-                let value = runtime.stack_pop();
-                runtime.frame().store_var(*ident as u16, value);
+                let Some(value) = runtime.stack_pop() else {
+                    return;
+                };
+                runtime.store_var(*ident as u16, value);
             }
             MemoryBlock::IINC(ident, to_add) => {
-                let current_value = runtime.frame().load_var(*ident as u16);
-                runtime
-                    .frame()
-                    .store_var(*ident as u16, current_value + *to_add as i32);
+                let Some(current_value) = runtime.load_var(*ident as u16) else {
+                    return;
+                };
+                let Some(result) = runtime.add(current_value, *to_add as i32) else {
+                    return;
+                };
+                runtime.store_var(*ident as u16, result);
             }
             MemoryBlock::WIDE(block) => match block {
                 WideMemoryBlock::ILOAD(ident) => {
-                    let value = runtime.frame().load_var(*ident);
+                    let Some(value) = runtime.load_var(*ident) else {
+                        return;
+                    };
                     runtime.stack_push(value);
                 }
                 WideMemoryBlock::ISTORE(ident) => {
-                    let value = runtime.stack_pop();
-                    runtime.frame().store_var(*ident, value);
+                    let Some(value) = runtime.stack_pop() else {
+                        return;
+                    };
+                    runtime.store_var(*ident, value);
                 }
                 WideMemoryBlock::IIINC(ident, to_add) => {
-                    let current_value = runtime.frame().load_var(*ident as u16);
-                    runtime
-                        .frame()
-                        .store_var(*ident as u16, current_value + *to_add as i32);
+                    let Some(current_value) = runtime.load_var(*ident) else {
+                        return;
+                    };
+                    let Some(result) = runtime.add(current_value, *to_add as i32) else {
+                        return;
+                    };
+                    runtime.store_var(*ident, result);
                 }
             },
             MemoryBlock::RESOLVED_INVOKEVIRTUAL(ind) => {
@@ -395,21 +662,41 @@ impl MemoryBlock {
                 }
                 #[cfg(not(feature = "unsafe"))]
                 {
-                    instruction = runtime.visit_instructions().get(*ind as usize).unwrap();
+                    instruction = runtime.visit_instructions().get(*ind).unwrap();
                 }
 
                 // this should be a method ref
                 let (n_args, n_vars) = match *instruction {
                     MemoryBlock::METHODHEADER { n_args, n_vars } => (n_args, n_vars),
-                    ref m => panic!(
-                        "INVOKEVIRTUAL points at something thats not a METHODHEADER, its a {:?}",
-                        m
-                    ),
+                    MemoryBlock::NATIVE { native, n_args } => {
+                        if O::ENABLED {
+                            let call_site = runtime.program_counter();
+                            runtime.observer().on_call(call_site, *ind);
+                        }
+                        let returned = runtime.call_native(native, n_args);
+                        // like IRETURN, from the method to the call, with the native
+                        // standing in for the IRETURN. One that stopped didn't return
+                        if O::ENABLED {
+                            if let Some(value) = returned {
+                                let call_site = runtime.program_counter();
+                                runtime.observer().on_return(*ind, call_site, value);
+                            }
+                        }
+                        return;
+                    }
+                    _ => return runtime.trap(TrapKind::BadMethod),
                 };
 
-                runtime.push_frame(n_vars, n_args);
+                if !runtime.push_frame(*ind, n_vars, n_args) {
+                    return;
+                }
 
+                if O::ENABLED {
+                    let call_site = runtime.program_counter();
+                    runtime.observer().on_call(call_site, *ind);
+                }
                 runtime.set_pc(*ind);
+                runtime.check_interrupt();
             }
             MemoryBlock::IRETURN => {
                 /*
@@ -428,17 +715,100 @@ impl MemoryBlock {
                     break;
                 */
 
-                let return_value = runtime.stack_pop();
-                runtime.pop_frame();
+                let Some(return_value) = runtime.stack_pop() else {
+                    return;
+                };
+                let from = runtime.program_counter();
+                if !runtime.pop_frame() {
+                    // main has nowhere to return to, the value stays where it was
+                    return runtime.stack_push(return_value);
+                }
+                if O::ENABLED {
+                    let to = runtime.program_counter();
+                    runtime.observer().on_return(from, to, return_value);
+                }
                 runtime.stack_push(return_value);
             }
 
-            MemoryBlock::ERR => {
-                panic!("Encountered ERR instruction");
-            }
+            MemoryBlock::ERR => runtime.trap(TrapKind::Err),
             MemoryBlock::NOP => {}
 
-            i => todo!("{:?}", i),
+            MemoryBlock::Delayed(ResolveLater::INVOKEVIRTUAL(_)) => {
+                runtime.trap(TrapKind::BadMethod)
+            }
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::Delayed(ResolveLater::SPAWN(_)) => runtime.trap(TrapKind::BadMethod),
+            MemoryBlock::Delayed(_) => runtime.trap(TrapKind::BadBranch),
+            // method headers are skipped by INVOKEVIRTUAL, and unknown opcodes parse as one
+            MemoryBlock::METHODHEADER { .. } | MemoryBlock::NATIVE { .. } => {
+                runtime.trap(TrapKind::InvalidOpcode)
+            }
+            MemoryBlock::END => runtime.end_of_program(),
+            MemoryBlock::BREAKPOINT => runtime.hit_breakpoint(),
+            #[cfg(feature = "checked")]
+            MemoryBlock::INVALID(_) => runtime.trap(TrapKind::InvalidOpcode),
+        }
+    }
+}
+
+impl MemoryBlock {
+    /// The opcode this was decoded from. Markers that aren't in the binary report the
+    /// opcode of what they act like: HALT for END, NOP for BREAKPOINT.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            MemoryBlock::BIPUSH(_) => 0x10,
+            MemoryBlock::DUP => 0x59,
+            MemoryBlock::ERR => 0xFE,
+            MemoryBlock::HALT => 0xFF,
+            MemoryBlock::IADD => 0x60,
+            MemoryBlock::IAND => 0x7E,
+            MemoryBlock::IINC(_, _) => 0x84,
+            MemoryBlock::ILOAD(_) => 0x15,
+            MemoryBlock::IN => 0xFC,
+            MemoryBlock::IOR => 0xB0,
+            MemoryBlock::IRETURN => 0xAC,
+            MemoryBlock::ISTORE(_) => 0x36,
+            MemoryBlock::ISUB => 0x64,
+            MemoryBlock::NOP => 0x00,
+            MemoryBlock::OUT => 0xFD,
+            MemoryBlock::POP => 0x57,
+            MemoryBlock::SWAP => 0x5F,
+            MemoryBlock::WIDE(_) => 0xC4,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IMUL => 0x68,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IDIV => 0x6C,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IREM => 0x70,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::INEG => 0x74,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHL => 0x78,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHR => 0x7A,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IUSHR => 0x7C,
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IXOR => 0x82,
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::RESOLVED_SPAWN(_) | MemoryBlock::Delayed(ResolveLater::SPAWN(_)) => 0xF0,
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::YIELD => 0xF1,
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::JOIN => 0xF2,
+            MemoryBlock::METHODHEADER { .. } | MemoryBlock::NATIVE { .. } => 0x00,
+            MemoryBlock::RESOLVED_INVOKEVIRTUAL(_)
+            | MemoryBlock::Delayed(ResolveLater::INVOKEVIRTUAL(_)) => 0xB6,
+            MemoryBlock::RESOLVED_GOTO(_) | MemoryBlock::Delayed(ResolveLater::GOTO(_)) => 0xA7,
+            MemoryBlock::RESOLVED_IFEQ(_) | MemoryBlock::Delayed(ResolveLater::IFEQ(_)) => 0x99,
+            MemoryBlock::RESOLVED_IFLT(_) | MemoryBlock::Delayed(ResolveLater::IFLT(_)) => 0x9B,
+            MemoryBlock::RESOLVED_IF_ICMPEQ(_)
+            | MemoryBlock::Delayed(ResolveLater::IF_ICMPEQ(_)) => 0x9F,
+            MemoryBlock::RESOLVED_LDC_W(_) => 0x13,
+            MemoryBlock::END => 0xFF,
+            MemoryBlock::BREAKPOINT => 0x00,
+            #[cfg(feature = "checked")]
+            MemoryBlock::INVALID(opcode) => *opcode,
         }
     }
 }
@@ -488,7 +858,30 @@ impl MemoryBlock {
             MemoryBlock::POP => "POP",
             MemoryBlock::SWAP => "SWAP",
             MemoryBlock::WIDE(_) => "WIDE",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IMUL => "IMUL",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IDIV => "IDIV",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IREM => "IREM",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHL => "ISHL",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::ISHR => "ISHR",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IUSHR => "IUSHR",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::IXOR => "IXOR",
+            #[cfg(feature = "ext-arith")]
+            MemoryBlock::INEG => "INEG",
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::RESOLVED_SPAWN(_) => "RESOLVED_SPAWN",
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::YIELD => "YIELD",
+            #[cfg(feature = "green-threads")]
+            MemoryBlock::JOIN => "JOIN",
             MemoryBlock::METHODHEADER { .. } => "METHODHEADER",
+            MemoryBlock::NATIVE { .. } => "NATIVE",
             MemoryBlock::RESOLVED_INVOKEVIRTUAL(_) => "RESOLVED_INVOKEVIRTUAL",
             MemoryBlock::RESOLVED_GOTO(_) => "RESOLVED_GOTO",
             MemoryBlock::RESOLVED_IFEQ(_) => "RESOLVED_IFEQ",
@@ -496,6 +889,10 @@ impl MemoryBlock {
             MemoryBlock::RESOLVED_IF_ICMPEQ(_) => "RESOLVED_IF_ICMPEQ",
             MemoryBlock::RESOLVED_LDC_W(_) => "RESOLVED_LDC_W",
             MemoryBlock::Delayed(_) => "Delayed",
+            MemoryBlock::END => "END",
+            MemoryBlock::BREAKPOINT => "BREAKPOINT",
+            #[cfg(feature = "checked")]
+            MemoryBlock::INVALID(_) => "INVALID",
         }
     }
 }
@@ -0,0 +1,28 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Asks a running [`crate::ijvm_core::Runtime`] to stop, from any thread.
+///
+/// The runtime checks for a request at backward branches and calls, and stops with
+/// [`crate::ijvm_core::Outcome::Interrupted`]. A request made while the runtime isn't
+/// running is picked up by the next run.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> InterruptHandle {
+        InterruptHandle::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Consumes a pending request.
+    #[inline]
+    pub(crate) fn take(&self) -> bool {
+        self.requested.load(Ordering::Relaxed) && self.requested.swap(false, Ordering::Relaxed)
+    }
+}
@@ -1,4 +1,27 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod async_io;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "std")]
+pub mod compat;
+pub mod fuel;
 pub mod ijvm;
 pub mod ijvm_core;
 pub mod instructions;
+// needs `Arc` and atomic swaps, which targets like thumbv6m-none-eabi lack
+#[cfg(target_has_atomic = "ptr")]
+pub mod interrupt;
+pub mod io;
+pub mod native;
+pub mod observer;
+pub mod replay;
+pub mod snapshot;
+#[cfg(feature = "green-threads")]
+pub mod threads;
 pub mod tiny;
+pub mod trap;
@@ -1,20 +1,54 @@
-use copp_rs::ijvm_core::init_ijvm;
+use copp_rs::{
+    batch::Batch,
+    ijvm_core::{init_ijvm, Outcome, Program},
+    replay::{Recorder, Recording},
+};
+
+const USAGE: &str = "usage: copp_rs batch <program.ijvm> <input>... [--threads N] [--limit N] [--out-dir DIR]
+       copp_rs record <program.ijvm> <recording>
+       copp_rs replay <program.ijvm> <recording>";
 
 fn main() {
-    let mut runtime = init_ijvm("files/advanced/mandelbread.ijvm");
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("batch") => return batch(&args[1..]),
+        Some("record") => return record(&args[1..]),
+        Some("replay") => return replay(&args[1..]),
+        _ => {}
+    }
+
+    // the output isn't interesting when running this repeatedly
+    let mut runtime = init_ijvm("files/advanced/mandelbread.ijvm").with_output(std::io::sink());
+
+    // the first Ctrl-C stops the VM cleanly, a second one kills the process
+    let interrupt = runtime.interrupt_handle();
+    let mut interrupted = false;
+    ctrlc::set_handler(move || {
+        if interrupted {
+            std::process::exit(130);
+        }
+        interrupted = true;
+        interrupt.interrupt();
+    })
+    .unwrap();
 
     println!("Starting execution");
 
     #[cfg(feature = "metrics")]
     {
-        runtime.run();
+        if runtime.run() == Outcome::Interrupted {
+            println!("Interrupted");
+        }
         runtime.inner.metrics.print();
     }
 
     #[cfg(not(feature = "metrics"))]
     {
         for _ in 0..10 {
-            runtime.run();
+            if runtime.run() == Outcome::Interrupted {
+                println!("Interrupted");
+                break;
+            }
             // reset
             runtime.reset();
         }
@@ -27,3 +61,131 @@ fn main() {
         // println!("{}", runtime.inner.visit_stack()[i]);
     }
 }
+
+/// Runs a program against every input file, printing one line per input:
+/// the file, outcome, instruction count and time. Outputs go to `--out-dir`,
+/// named after the input file with an `.out` extension; inputs that would share
+/// an output file are refused before anything runs.
+fn batch(args: &[String]) {
+    let mut threads = None;
+    let mut limit = None;
+    let mut out_dir = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => threads = Some(flag_value(args.next())),
+            "--limit" => limit = Some(flag_value(args.next())),
+            "--out-dir" => out_dir = Some(flag_value::<String>(args.next())),
+            _ => files.push(arg.as_str()),
+        }
+    }
+    if files.len() < 2 {
+        usage_error();
+    }
+
+    let out_paths = out_dir.as_ref().map(|out_dir| {
+        let out_paths = files[1..]
+            .iter()
+            .map(|file| {
+                let name = std::path::Path::new(file).file_name().unwrap();
+                std::path::Path::new(out_dir)
+                    .join(name)
+                    .with_extension("out")
+            })
+            .collect::<Vec<_>>();
+        for (i, path) in out_paths.iter().enumerate() {
+            if let Some(other) = out_paths[..i].iter().position(|other| other == path) {
+                eprintln!(
+                    "{} and {} would both write {}",
+                    files[1 + other],
+                    files[1 + i],
+                    path.display()
+                );
+                std::process::exit(2);
+            }
+        }
+        out_paths
+    });
+
+    let program = or_exit(files[0], Program::try_load(files[0]));
+    let inputs = files[1..]
+        .iter()
+        .map(|file| or_exit(file, std::fs::read(file)))
+        .collect::<Vec<_>>();
+
+    let mut batch = Batch::new(&program);
+    if let Some(threads) = threads {
+        batch = batch.with_threads(threads);
+    }
+    if let Some(limit) = limit {
+        batch = batch.with_instruction_limit(limit);
+    }
+
+    for (i, (file, result)) in files[1..].iter().zip(batch.run(&inputs)).enumerate() {
+        println!(
+            "{}\t{:?}\t{}\t{:?}",
+            file, result.outcome, result.instructions, result.time
+        );
+        if let Some(out_paths) = &out_paths {
+            let path = &out_paths[i];
+            or_exit(&path.display().to_string(), std::fs::write(path, &result.output));
+        }
+    }
+}
+
+/// Runs a program on stdin like usual, and writes what it read and wrote to
+/// the recording file.
+fn record(args: &[String]) {
+    let [program, recording] = args else {
+        usage_error();
+    };
+    let program = or_exit(program, Program::try_load(program));
+    let mut runtime = program.runtime().with_observer(Recorder::new());
+    let outcome = runtime.run();
+    let bytes = runtime.observer().recording().to_bytes();
+    or_exit(recording, std::fs::write(recording, bytes));
+    eprintln!("{:?}", outcome);
+}
+
+/// Runs a program on the input of a recording, and checks that it writes the
+/// same. Exits with 1 if it doesn't.
+fn replay(args: &[String]) {
+    let [program, recording] = args else {
+        usage_error();
+    };
+    let bytes = or_exit(recording, std::fs::read(recording));
+    let recording = or_exit(recording, Recording::from_bytes(&bytes));
+    let program = or_exit(program, Program::try_load(program));
+    let mut runtime = program.runtime().with_output(Vec::new());
+    let result = runtime.replay(&recording);
+    std::io::Write::write_all(&mut std::io::stderr(), &runtime.inner.out_stream).unwrap();
+    match result {
+        Ok(outcome) => eprintln!("{:?}", outcome),
+        Err(e) => {
+            eprintln!("replay diverged: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn flag_value<T: std::str::FromStr>(value: Option<&String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => usage_error(),
+    }
+}
+
+/// The value, or exits with 1 after printing what went wrong with `path`.
+fn or_exit<T, E: std::fmt::Display>(path: &str, result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
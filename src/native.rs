@@ -0,0 +1,17 @@
+use alloc::boxed::Box;
+
+use crate::{ijvm_core::RuntimeInner, instructions::MemoryBlock};
+
+/// A host function standing in for an IJVM method, see
+/// [`crate::ijvm_core::Runtime::register_native`]. It gets the runtime and the
+/// call's arguments without OBJREF, and returns the method's return value. It can
+/// stop execution through the runtime, e.g. with [`RuntimeInner::trap`].
+pub type NativeFn<R, W, O> = Box<dyn FnMut(&mut RuntimeInner<R, W, O>, &[i32]) -> i32 + Send>;
+
+/// A registered native and the method header it replaced.
+pub(crate) struct Native<R, W, O> {
+    pub(crate) header: usize,
+    pub(crate) original: MemoryBlock,
+    // taken out while it runs, so it can borrow the runtime
+    pub(crate) function: Option<NativeFn<R, W, O>>,
+}
@@ -0,0 +1,67 @@
+use crate::{ijvm_core::InstructionRef, instructions::MemoryBlock, trap::Trap};
+
+#[cfg(feature = "green-threads")]
+use crate::threads::ThreadId;
+
+/// Gets told what a [`crate::ijvm_core::Runtime`] does, for tracers, coverage and
+/// the like. Set one with [`crate::ijvm_core::Runtime::with_observer`].
+///
+/// Every callback does nothing by default, so an observer only implements what it
+/// needs. Program counters are indices into the instructions.
+pub trait Observer {
+    /// Whether the runtime calls the callbacks at all. Only [`NoopObserver`] turns
+    /// this off, which takes every trace of observing out of the compiled runtime.
+    const ENABLED: bool = true;
+
+    /// `instruction` at `pc` is about to execute.
+    #[inline]
+    fn before_instruction(&mut self, _pc: InstructionRef, _instruction: &MemoryBlock) {}
+
+    /// `instruction` at `pc` has executed.
+    #[inline]
+    fn after_instruction(&mut self, _pc: InstructionRef, _instruction: &MemoryBlock) {}
+
+    /// `INVOKEVIRTUAL` at `call_site` entered the method whose header is at `method`.
+    #[inline]
+    fn on_call(&mut self, _call_site: InstructionRef, _method: InstructionRef) {}
+
+    /// `IRETURN` at `from` returned `value` to the call at `to`. For a native method,
+    /// `from` is its header. A method that stops execution, e.g. with a trap, doesn't
+    /// return.
+    #[inline]
+    fn on_return(&mut self, _from: InstructionRef, _to: InstructionRef, _value: i32) {}
+
+    /// The branch at `from` jumped to `to`. Branches not taken aren't reported.
+    #[inline]
+    fn on_branch(&mut self, _from: InstructionRef, _to: InstructionRef) {}
+
+    /// `IN` read `byte`, or `None` at the end of input.
+    #[inline]
+    fn on_input(&mut self, _byte: Option<u8>) {}
+
+    /// `OUT` wrote `byte`.
+    #[inline]
+    fn on_output(&mut self, _byte: u8) {}
+
+    #[inline]
+    fn on_trap(&mut self, _trap: &Trap) {}
+
+    /// `SPAWN` started `thread` in the method whose header is at `method`.
+    #[cfg(feature = "green-threads")]
+    #[inline]
+    fn on_spawn(&mut self, _thread: ThreadId, _method: InstructionRef) {}
+
+    /// The processor went from thread `from` to thread `to`. Everything reported
+    /// after this happens on `to`, until the next switch.
+    #[cfg(feature = "green-threads")]
+    #[inline]
+    fn on_thread_switch(&mut self, _from: ThreadId, _to: ThreadId) {}
+}
+
+/// The default observer, which compiles away.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {
+    const ENABLED: bool = false;
+}
@@ -0,0 +1,255 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{
+    ijvm_core::{InputMode, InstructionRef, Outcome, Runtime},
+    instructions::MemoryBlock,
+    io::Read,
+    observer::Observer,
+    snapshot::{Reader, SnapshotError},
+};
+
+const MAGIC: &[u8; 4] = b"IJVR";
+const VERSION: u16 = 1;
+
+/// A byte `IN` consumed, or the end of input it hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedInput {
+    /// Instructions executed before the `IN`.
+    pub at: u64,
+    pub byte: Option<u8>,
+}
+
+/// What a run read and wrote, made by a [`Recorder`] and checked by
+/// [`Runtime::replay`]. The NET instructions aren't implemented, so only `IN`
+/// is recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    input: Vec<RecordedInput>,
+    output: Vec<u8>,
+}
+
+/// Records a run when set as the runtime's observer with
+/// [`Runtime::with_observer`]. The input can come from anywhere, e.g. a live stdin.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    recording: Recording,
+    executed: u64,
+    // whether the instruction executing consumed input
+    read: bool,
+}
+
+/// How a replay went differently than its recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// `IN` waited for input after `read_at` instructions, but the next input was
+    /// recorded at `recorded`, or there was none left.
+    InputMismatch { recorded: Option<u64>, read_at: u64 },
+    /// Execution stopped before `IN` consumed the input recorded at `recorded`.
+    InputLeft { recorded: u64 },
+    /// The output first differs from the recorded one at byte `offset`.
+    OutputMismatch { offset: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InputMismatch {
+                recorded: Some(recorded),
+                read_at,
+            } => write!(
+                f,
+                "input read after {} instructions, recorded after {}",
+                read_at, recorded
+            ),
+            ReplayError::InputMismatch {
+                recorded: None,
+                read_at,
+            } => write!(
+                f,
+                "input read after {} instructions, past the recorded input",
+                read_at
+            ),
+            ReplayError::InputLeft { recorded } => write!(
+                f,
+                "stopped before reading the input recorded after {} instructions",
+                recorded
+            ),
+            ReplayError::OutputMismatch { offset } => {
+                write!(f, "output differs from the recording at byte {}", offset)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ReplayError {}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn into_recording(self) -> Recording {
+        self.recording
+    }
+}
+
+impl Observer for Recorder {
+    fn before_instruction(&mut self, _pc: InstructionRef, _instruction: &MemoryBlock) {
+        self.read = false;
+    }
+
+    fn after_instruction(&mut self, _pc: InstructionRef, instruction: &MemoryBlock) {
+        match instruction {
+            // neither executes, an IN that waited runs again once there is input
+            MemoryBlock::BREAKPOINT => {}
+            MemoryBlock::IN if !self.read => {}
+            _ => self.executed += 1,
+        }
+    }
+
+    fn on_input(&mut self, byte: Option<u8>) {
+        self.recording.input.push(RecordedInput {
+            at: self.executed,
+            byte,
+        });
+        self.read = true;
+    }
+
+    fn on_output(&mut self, byte: u8) {
+        self.recording.output.push(byte);
+    }
+}
+
+impl Recording {
+    /// The input in the order it was consumed.
+    pub fn input(&self) -> &[RecordedInput] {
+        &self.input
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Encodes the recording, big-endian and versioned like a
+    /// [`crate::snapshot::Snapshot`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_be_bytes());
+
+        out.extend_from_slice(&(self.input.len() as u64).to_be_bytes());
+        for input in &self.input {
+            out.extend_from_slice(&input.at.to_be_bytes());
+            match input.byte {
+                Some(byte) => out.extend_from_slice(&[1, byte]),
+                None => out.push(0),
+            }
+        }
+
+        out.extend_from_slice(&(self.output.len() as u64).to_be_bytes());
+        out.extend_from_slice(&self.output);
+        out
+    }
+
+    /// Decodes a recording, failing like [`crate::snapshot::Snapshot::from_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording, SnapshotError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let input_len = reader.u64()?;
+        // every input takes at least 9 bytes, check before allocating for them
+        if (reader.bytes.len() / 9) < input_len as usize {
+            return Err(SnapshotError::Truncated);
+        }
+        let mut input = Vec::with_capacity(input_len as usize);
+        for _ in 0..input_len {
+            let at = reader.u64()?;
+            let byte = match reader.u8()? {
+                0 => None,
+                1 => Some(reader.u8()?),
+                _ => return Err(SnapshotError::Corrupt("recorded input")),
+            };
+            input.push(RecordedInput { at, byte });
+        }
+
+        let output_len =
+            usize::try_from(reader.u64()?).map_err(|_| SnapshotError::Corrupt("output length"))?;
+        let output = reader.take(output_len)?.to_vec();
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Corrupt("trailing bytes"));
+        }
+        Ok(Recording { input, output })
+    }
+}
+
+impl<R: Read, O: Observer> Runtime<R, Vec<u8>, O> {
+    /// Runs the program on the input of `recording`, handing every byte to `IN` at
+    /// the instruction it was recorded at, and checks the output against the
+    /// recording. Meant for a freshly loaded or reset runtime with the same
+    /// [`crate::ijvm_core::EofPolicy`] as the recorded one. Breakpoints are
+    /// ignored.
+    ///
+    /// Returns how execution stopped, or how it went differently. Either way the
+    /// output is in the runtime's output buffer.
+    pub fn replay(&mut self, recording: &Recording) -> Result<Outcome, ReplayError> {
+        let mode = self.input_mode();
+        self.set_input_mode(InputMode::NonBlocking);
+        let output_start = self.inner.out_stream.len();
+
+        let mut executed = 0u64;
+        let mut input = recording.input.iter();
+        let mut closed = false;
+        let result = loop {
+            match self.step() {
+                Outcome::Running => executed += 1,
+                // executes on the next step
+                Outcome::Breakpoint => {}
+                Outcome::NeedsInput => match input.next() {
+                    Some(next) if next.at == executed => match next.byte {
+                        Some(byte) => self.feed_input(&[byte]),
+                        None => {
+                            // later reads can't wait anymore, they all hit the end
+                            self.close_input();
+                            closed = true;
+                        }
+                    },
+                    next => {
+                        break Err(ReplayError::InputMismatch {
+                            recorded: next.map(|next| next.at),
+                            read_at: executed,
+                        })
+                    }
+                },
+                outcome => break Ok(outcome),
+            }
+        };
+        self.set_input_mode(mode);
+
+        let outcome = result?;
+        if let Some(left) = input.find(|left| !closed || left.byte.is_some()) {
+            return Err(ReplayError::InputLeft { recorded: left.at });
+        }
+        let output = &self.inner.out_stream[output_start..];
+        if output != recording.output {
+            let offset = output
+                .iter()
+                .zip(&recording.output)
+                .position(|(a, b)| a != b)
+                .unwrap_or(output.len().min(recording.output.len()));
+            return Err(ReplayError::OutputMismatch { offset });
+        }
+        Ok(outcome)
+    }
+}
@@ -0,0 +1,541 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+use crate::{
+    ijvm_core::{InstructionRef, Outcome},
+    io::ErrorKind,
+    trap::{Trap, TrapKind},
+};
+
+const MAGIC: &[u8; 4] = b"IJVS";
+// version 1 didn't have frame methods, version 2 whether a breakpoint was taken,
+// version 3 green threads
+const VERSION: u16 = 4;
+// a frame method that isn't known
+const NO_METHOD: u64 = u64::MAX;
+// NoReturn traps an outcome can sit in, a NoReturn never holds another
+const MAX_NESTING: u8 = 1;
+
+// I/O error kinds a trap can carry, by their index in the encoding
+const IO_KINDS: [ErrorKind; 20] = [
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::ConnectionRefused,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+    ErrorKind::AddrInUse,
+    ErrorKind::AddrNotAvailable,
+    ErrorKind::BrokenPipe,
+    ErrorKind::AlreadyExists,
+    ErrorKind::WouldBlock,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::TimedOut,
+    ErrorKind::WriteZero,
+    ErrorKind::Interrupted,
+    ErrorKind::Unsupported,
+    ErrorKind::UnexpectedEof,
+    ErrorKind::OutOfMemory,
+    ErrorKind::Other,
+];
+
+/// The state of a [`crate::ijvm_core::Runtime`] at one point of execution, see
+/// [`crate::ijvm_core::Runtime::snapshot`].
+///
+/// The program itself isn't part of it, only a fingerprint of it, so a snapshot is
+/// small and can only be restored into a runtime that loaded the same binary.
+/// Settings like the [`crate::ijvm_core::EofPolicy`] and the I/O streams aren't
+/// part of it either. With green threads it holds every thread, and only a build
+/// with them can restore it once the program has spawned one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub(crate) program: u64,
+    pub(crate) program_counter: InstructionRef,
+    pub(crate) outcome: Outcome,
+    pub(crate) stack: Vec<i32>,
+    pub(crate) top_value: i32,
+    pub(crate) frames: Vec<FrameState>,
+    pub(crate) input_consumed: u64,
+    pub(crate) input_pending: Vec<u8>,
+    pub(crate) input_closed: bool,
+    // the instruction at the pc is under a breakpoint that already stopped it
+    pub(crate) breakpoint_taken: bool,
+    // None until a thread is spawned, the fields above are the running thread's
+    pub(crate) threads: Option<ThreadsState>,
+}
+
+/// The green threads of a runtime, see [`crate::threads`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ThreadsState {
+    pub(crate) current: usize,
+    // instructions left in the current time slice
+    pub(crate) remaining: u32,
+    pub(crate) threads: Vec<ThreadState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ThreadState {
+    pub(crate) status: ThreadStatus,
+    // None for the running thread and finished ones
+    pub(crate) context: Option<ContextState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ThreadStatus {
+    Ready,
+    Joining(usize),
+    Finished(i32),
+}
+
+/// The state of a thread that isn't running.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ContextState {
+    pub(crate) program_counter: InstructionRef,
+    pub(crate) stack: Vec<i32>,
+    pub(crate) top_value: i32,
+    pub(crate) frames: Vec<FrameState>,
+}
+
+/// One [`crate::ijvm::Frame`], main first.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FrameState {
+    pub(crate) starting_stack_length: u32,
+    pub(crate) restore_pc: InstructionRef,
+    // unknown in version 1 snapshots
+    pub(crate) method: Option<InstructionRef>,
+    pub(crate) vars: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The bytes don't start like a snapshot.
+    BadMagic,
+    /// The snapshot was written by a newer encoding.
+    UnsupportedVersion(u16),
+    /// The bytes end in the middle of the snapshot.
+    Truncated,
+    /// A field holds a value no runtime can be in.
+    Corrupt(&'static str),
+    /// The snapshot was taken from a different program.
+    ProgramMismatch,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::Corrupt(what) => write!(f, "snapshot is corrupt: {}", what),
+            SnapshotError::ProgramMismatch => write!(f, "snapshot is of a different program"),
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+impl Snapshot {
+    #[inline]
+    pub fn program_counter(&self) -> InstructionRef {
+        self.program_counter
+    }
+
+    /// How the runtime had stopped, [`Outcome::Running`] if it hadn't.
+    #[inline]
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    /// Number of input bytes `IN` had consumed, fed or read from the input stream.
+    /// Restoring doesn't touch the input stream, the host has to position it here.
+    #[inline]
+    pub fn input_position(&self) -> u64 {
+        self.input_consumed
+    }
+
+    /// Encodes the snapshot. The encoding is big-endian and versioned, so bytes
+    /// written by one build can be read by any later one.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_be_bytes());
+        out.extend_from_slice(&self.program.to_be_bytes());
+        out.extend_from_slice(&(self.program_counter as u64).to_be_bytes());
+        write_outcome(&mut out, &self.outcome);
+
+        out.extend_from_slice(&self.top_value.to_be_bytes());
+        write_values(&mut out, &self.stack);
+
+        write_frames(&mut out, &self.frames);
+
+        out.extend_from_slice(&self.input_consumed.to_be_bytes());
+        out.push(self.input_closed as u8);
+        out.extend_from_slice(&(self.input_pending.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.input_pending);
+        out.push(self.breakpoint_taken as u8);
+        match &self.threads {
+            None => out.push(0),
+            Some(threads) => {
+                out.push(1);
+                write_threads(&mut out, threads);
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version == 0 || version > VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let program = reader.u64()?;
+        let program_counter = reader.pc()?;
+        let outcome = reader.outcome(0)?;
+
+        let top_value = reader.i32()?;
+        let stack = reader.values()?;
+
+        let frames = reader.frames(version)?;
+
+        let input_consumed = reader.u64()?;
+        let input_closed = match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(SnapshotError::Corrupt("input closed flag")),
+        };
+        let pending_len = reader.u32()? as usize;
+        let input_pending = reader.take(pending_len)?.to_vec();
+        let breakpoint_taken = match version {
+            1 | 2 => false,
+            _ => match reader.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(SnapshotError::Corrupt("breakpoint taken flag")),
+            },
+        };
+        let threads = match version {
+            1..=3 => None,
+            _ => match reader.u8()? {
+                0 => None,
+                1 => Some(reader.threads(version)?),
+                _ => return Err(SnapshotError::Corrupt("threads flag")),
+            },
+        };
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Corrupt("trailing bytes"));
+        }
+
+        Ok(Snapshot {
+            program,
+            program_counter,
+            outcome,
+            stack,
+            top_value,
+            frames,
+            input_consumed,
+            input_pending,
+            input_closed,
+            breakpoint_taken,
+            threads,
+        })
+    }
+}
+
+fn write_values(out: &mut Vec<u8>, values: &[i32]) {
+    out.extend_from_slice(&(values.len() as u32).to_be_bytes());
+    for value in values {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn write_frames(out: &mut Vec<u8>, frames: &[FrameState]) {
+    out.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+    for frame in frames {
+        out.extend_from_slice(&frame.starting_stack_length.to_be_bytes());
+        out.extend_from_slice(&(frame.restore_pc as u64).to_be_bytes());
+        let method = frame.method.map_or(NO_METHOD, |method| method as u64);
+        out.extend_from_slice(&method.to_be_bytes());
+        write_values(out, &frame.vars);
+    }
+}
+
+fn write_threads(out: &mut Vec<u8>, threads: &ThreadsState) {
+    out.extend_from_slice(&(threads.current as u64).to_be_bytes());
+    out.extend_from_slice(&threads.remaining.to_be_bytes());
+    out.extend_from_slice(&(threads.threads.len() as u32).to_be_bytes());
+    for thread in &threads.threads {
+        match thread.status {
+            ThreadStatus::Ready => out.push(0),
+            ThreadStatus::Joining(other) => {
+                out.push(1);
+                out.extend_from_slice(&(other as u64).to_be_bytes());
+            }
+            ThreadStatus::Finished(value) => {
+                out.push(2);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        match &thread.context {
+            None => out.push(0),
+            Some(context) => {
+                out.push(1);
+                out.extend_from_slice(&(context.program_counter as u64).to_be_bytes());
+                out.extend_from_slice(&context.top_value.to_be_bytes());
+                write_values(out, &context.stack);
+                write_frames(out, &context.frames);
+            }
+        }
+    }
+}
+
+fn write_outcome(out: &mut Vec<u8>, outcome: &Outcome) {
+    let tag = match outcome {
+        Outcome::Running => 0,
+        Outcome::Halted => 1,
+        Outcome::EndOfProgram => 2,
+        Outcome::Trapped(_) => 3,
+        Outcome::NeedsInput => 4,
+        Outcome::BudgetExhausted => 5,
+        Outcome::Breakpoint => 6,
+        Outcome::Interrupted => 7,
+    };
+    out.push(tag);
+
+    if let Outcome::Trapped(trap) = outcome {
+        let tag = match trap.kind {
+            TrapKind::Err => 0,
+            TrapKind::StackUnderflow => 1,
+            TrapKind::BadMethod => 2,
+            TrapKind::BadBranch => 3,
+            TrapKind::ReturnFromMain => 4,
+            TrapKind::InvalidOpcode => 5,
+            TrapKind::Overflow => 6,
+            TrapKind::DivisionByZero => 7,
+            TrapKind::Io(_) => 8,
+            TrapKind::EndOfInput => 9,
+            TrapKind::StackOverflow => 10,
+            TrapKind::CallDepthExceeded { .. } => 11,
+            TrapKind::NoReturn(_) => 12,
+            TrapKind::BadThread => 13,
+            TrapKind::Deadlock => 14,
+            TrapKind::BadLocal => 15,
+        };
+        out.push(tag);
+        if let TrapKind::CallDepthExceeded { backtrace } = &trap.kind {
+            out.extend_from_slice(&(backtrace.len() as u32).to_be_bytes());
+            for pc in backtrace {
+                out.extend_from_slice(&(*pc as u64).to_be_bytes());
+            }
+        }
+        if let TrapKind::NoReturn(outcome) = &trap.kind {
+            write_outcome(out, outcome);
+        }
+        if let TrapKind::Io(kind) = trap.kind {
+            // kinds without a stable index are recorded as Other
+            let index = IO_KINDS
+                .iter()
+                .position(|known| *known == kind)
+                .unwrap_or(IO_KINDS.len() - 1);
+            out.push(index as u8);
+        }
+        out.extend_from_slice(&(trap.pc as u64).to_be_bytes());
+        out.extend_from_slice(&(trap.call_depth as u64).to_be_bytes());
+    }
+}
+
+// also reads recordings, see crate::replay
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < n {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, SnapshotError> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn pc(&mut self) -> Result<InstructionRef, SnapshotError> {
+        InstructionRef::try_from(self.u64()?)
+            .map_err(|_| SnapshotError::Corrupt("instruction index"))
+    }
+
+    fn thread_id(&mut self) -> Result<usize, SnapshotError> {
+        usize::try_from(self.u64()?).map_err(|_| SnapshotError::Corrupt("thread id"))
+    }
+
+    fn values(&mut self) -> Result<Vec<i32>, SnapshotError> {
+        let len = self.u32()? as usize;
+        // check the length against what is left before allocating for it
+        if self.bytes.len() / 4 < len {
+            return Err(SnapshotError::Truncated);
+        }
+        (0..len).map(|_| self.i32()).collect()
+    }
+
+    fn frames(&mut self, version: u16) -> Result<Vec<FrameState>, SnapshotError> {
+        let count = self.u32()?;
+        let mut frames = Vec::new();
+        for _ in 0..count {
+            let starting_stack_length = self.u32()?;
+            let restore_pc = self.pc()?;
+            let method = match version {
+                1 => None,
+                _ => match self.u64()? {
+                    NO_METHOD => None,
+                    method => Some(
+                        InstructionRef::try_from(method)
+                            .map_err(|_| SnapshotError::Corrupt("frame method"))?,
+                    ),
+                },
+            };
+            frames.push(FrameState {
+                starting_stack_length,
+                restore_pc,
+                method,
+                vars: self.values()?,
+            });
+        }
+        Ok(frames)
+    }
+
+    fn threads(&mut self, version: u16) -> Result<ThreadsState, SnapshotError> {
+        let current = self.thread_id()?;
+        let remaining = self.u32()?;
+        let count = self.u32()?;
+        let mut threads = Vec::new();
+        for _ in 0..count {
+            let status = match self.u8()? {
+                0 => ThreadStatus::Ready,
+                1 => ThreadStatus::Joining(self.thread_id()?),
+                2 => ThreadStatus::Finished(self.i32()?),
+                _ => return Err(SnapshotError::Corrupt("thread status")),
+            };
+            let context = match self.u8()? {
+                0 => None,
+                1 => Some(ContextState {
+                    program_counter: self.pc()?,
+                    top_value: self.i32()?,
+                    stack: self.values()?,
+                    frames: self.frames(version)?,
+                }),
+                _ => return Err(SnapshotError::Corrupt("thread state flag")),
+            };
+            threads.push(ThreadState { status, context });
+        }
+        Ok(ThreadsState {
+            current,
+            remaining,
+            threads,
+        })
+    }
+
+    /// Reads an outcome `nesting` NoReturn traps deep.
+    fn outcome(&mut self, nesting: u8) -> Result<Outcome, SnapshotError> {
+        Ok(match self.u8()? {
+            0 => Outcome::Running,
+            1 => Outcome::Halted,
+            2 => Outcome::EndOfProgram,
+            3 => Outcome::Trapped(self.trap(nesting)?),
+            4 => Outcome::NeedsInput,
+            5 => Outcome::BudgetExhausted,
+            6 => Outcome::Breakpoint,
+            7 => Outcome::Interrupted,
+            _ => return Err(SnapshotError::Corrupt("outcome")),
+        })
+    }
+
+    fn trap(&mut self, nesting: u8) -> Result<Trap, SnapshotError> {
+        let kind = match self.u8()? {
+            0 => TrapKind::Err,
+            1 => TrapKind::StackUnderflow,
+            2 => TrapKind::BadMethod,
+            3 => TrapKind::BadBranch,
+            4 => TrapKind::ReturnFromMain,
+            5 => TrapKind::InvalidOpcode,
+            6 => TrapKind::Overflow,
+            7 => TrapKind::DivisionByZero,
+            8 => match IO_KINDS.get(self.u8()? as usize) {
+                Some(kind) => TrapKind::Io(*kind),
+                None => return Err(SnapshotError::Corrupt("I/O error kind")),
+            },
+            9 => TrapKind::EndOfInput,
+            10 => TrapKind::StackOverflow,
+            11 => {
+                let len = self.u32()?;
+                let mut backtrace = Vec::new();
+                for _ in 0..len {
+                    backtrace.push(self.pc()?);
+                }
+                TrapKind::CallDepthExceeded { backtrace }
+            }
+            // the outcome the call stopped with
+            12 if nesting < MAX_NESTING => {
+                TrapKind::NoReturn(Box::new(self.outcome(nesting + 1)?))
+            }
+            12 => return Err(SnapshotError::Corrupt("nested trap")),
+            13 => TrapKind::BadThread,
+            14 => TrapKind::Deadlock,
+            15 => TrapKind::BadLocal,
+            _ => return Err(SnapshotError::Corrupt("trap kind")),
+        };
+        Ok(Trap {
+            kind,
+            pc: self.pc()?,
+            call_depth: self
+                .u64()?
+                .try_into()
+                .map_err(|_| SnapshotError::Corrupt("call depth"))?,
+        })
+    }
+}
+
+/// Identifies a program by its constants and text (64-bit FNV-1a).
+pub(crate) fn fingerprint(constants: &[i32], text: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    feed(&(constants.len() as u32).to_be_bytes());
+    for constant in constants {
+        feed(&constant.to_be_bytes());
+    }
+    feed(&(text.len() as u32).to_be_bytes());
+    feed(text);
+    hash
+}
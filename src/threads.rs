@@ -0,0 +1,219 @@
+// green threads for the SPAWN, YIELD and JOIN extension instructions
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    ijvm_core::InstructionRef,
+    snapshot::{ContextState, ThreadState, ThreadStatus, ThreadsState},
+    tiny::{FrameStack, Stack},
+};
+
+/// Identifies a green thread. Main is thread 0, spawned threads count up from 1.
+pub type ThreadId = usize;
+
+/// Instructions a thread runs before the next one gets its turn, unless it yields
+/// or waits first. Set with [`crate::ijvm_core::Runtime::set_time_slice`].
+pub const DEFAULT_TIME_SLICE: u32 = 1000;
+
+/// The state of a thread that isn't running. The running thread's lives in the runtime.
+pub(crate) struct Context {
+    pub(crate) stack: Stack,
+    pub(crate) frames: FrameStack,
+    pub(crate) program_counter: InstructionRef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Ready,
+    // waits in JOIN for the thread to finish
+    Joining(ThreadId),
+    Finished(i32),
+}
+
+struct Thread {
+    state: State,
+    // None while running, and once finished
+    context: Option<Context>,
+}
+
+/// Every thread of a runtime, scheduled round-robin in order of their ids.
+pub(crate) struct Threads {
+    threads: Vec<Thread>,
+    current: ThreadId,
+    time_slice: u32,
+    // instructions left in the current slice
+    remaining: u32,
+    // no switching, while the host calls a method
+    pub(crate) pinned: bool,
+}
+
+impl Threads {
+    pub(crate) fn new() -> Threads {
+        Threads {
+            threads: vec![Thread {
+                state: State::Ready,
+                context: None,
+            }],
+            current: 0,
+            time_slice: DEFAULT_TIME_SLICE,
+            remaining: DEFAULT_TIME_SLICE,
+            pinned: false,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn current(&self) -> ThreadId {
+        self.current
+    }
+
+    pub(crate) fn set_time_slice(&mut self, time_slice: u32) {
+        self.time_slice = time_slice.max(1);
+        self.remaining = self.time_slice;
+    }
+
+    /// Counts an instruction against the current slice, when there is anything to
+    /// switch to.
+    #[inline]
+    pub(crate) fn tick(&mut self) {
+        if self.threads.len() > 1 {
+            self.remaining = self.remaining.saturating_sub(1);
+        }
+    }
+
+    /// Whether the current slice is used up.
+    #[inline]
+    pub(crate) fn expired(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub(crate) fn spawn(&mut self, context: Context) -> ThreadId {
+        self.threads.push(Thread {
+            state: State::Ready,
+            context: Some(context),
+        });
+        self.threads.len() - 1
+    }
+
+    /// What `thread` returned: `None` if it doesn't exist, `Some(None)` if it's still running.
+    pub(crate) fn result(&self, thread: ThreadId) -> Option<Option<i32>> {
+        match self.threads.get(thread)?.state {
+            State::Finished(value) => Some(Some(value)),
+            _ => Some(None),
+        }
+    }
+
+    /// Makes the current thread wait for `thread` to finish.
+    pub(crate) fn join(&mut self, thread: ThreadId) {
+        self.threads[self.current].state = State::Joining(thread);
+    }
+
+    /// Marks the current thread as done, dropping its state once switched away from.
+    pub(crate) fn finish(&mut self, value: i32) {
+        self.threads[self.current].state = State::Finished(value);
+    }
+
+    fn runnable(&self, thread: ThreadId) -> bool {
+        match self.threads[thread].state {
+            State::Ready => true,
+            State::Joining(other) => matches!(self.threads[other].state, State::Finished(_)),
+            State::Finished(_) => false,
+        }
+    }
+
+    /// The next thread in turn that can run, the current one last.
+    pub(crate) fn next_runnable(&self) -> Option<ThreadId> {
+        let count = self.threads.len();
+        (1..=count)
+            .map(|i| (self.current + i) % count)
+            .find(|thread| self.runnable(*thread))
+    }
+
+    /// Makes `thread` the current one and takes out its state, which the caller
+    /// swaps with the runtime's, then hands back to [`Threads::store`].
+    pub(crate) fn switch(&mut self, thread: ThreadId) -> Context {
+        let incoming = self.threads[thread]
+            .context
+            .take()
+            .expect("switched to a thread without state");
+        self.threads[thread].state = State::Ready;
+        self.current = thread;
+        self.remaining = self.time_slice;
+        incoming
+    }
+
+    /// Keeps the state of a thread that was switched away from, unless it's finished.
+    pub(crate) fn store(&mut self, thread: ThreadId, context: Context) {
+        if !matches!(self.threads[thread].state, State::Finished(_)) {
+            self.threads[thread].context = Some(context);
+        }
+    }
+
+    /// Gives the current thread a fresh slice.
+    pub(crate) fn restart_slice(&mut self) {
+        self.remaining = self.time_slice;
+    }
+
+    /// Main's state, if it isn't the current thread.
+    pub(crate) fn main_context(&self) -> Option<&Context> {
+        self.threads[0].context.as_ref()
+    }
+
+    /// Every thread for a snapshot, None while main is the only one. `save` turns
+    /// the state of a thread that isn't running into a snapshot's.
+    pub(crate) fn save(&self, save: impl Fn(&Context) -> ContextState) -> Option<ThreadsState> {
+        if self.threads.len() == 1 {
+            return None;
+        }
+        let threads = self
+            .threads
+            .iter()
+            .map(|thread| ThreadState {
+                status: match thread.state {
+                    State::Ready => ThreadStatus::Ready,
+                    State::Joining(other) => ThreadStatus::Joining(other),
+                    State::Finished(value) => ThreadStatus::Finished(value),
+                },
+                context: thread.context.as_ref().map(&save),
+            })
+            .collect();
+        Some(ThreadsState {
+            current: self.current,
+            remaining: self.remaining,
+            threads,
+        })
+    }
+
+    /// Replaces every thread with ones from a snapshot, checked to fit together.
+    /// The running thread's state is the runtime's.
+    pub(crate) fn restore(
+        &mut self,
+        current: ThreadId,
+        remaining: u32,
+        threads: Vec<(ThreadStatus, Option<Context>)>,
+    ) {
+        self.threads = threads
+            .into_iter()
+            .map(|(status, context)| Thread {
+                state: match status {
+                    ThreadStatus::Ready => State::Ready,
+                    ThreadStatus::Joining(other) => State::Joining(other),
+                    ThreadStatus::Finished(value) => State::Finished(value),
+                },
+                context,
+            })
+            .collect();
+        self.current = current;
+        // the time slice is a setting, which may be shorter here
+        self.remaining = remaining.min(self.time_slice);
+    }
+
+    /// Drops every thread but main, returning main's state if it isn't the current thread.
+    pub(crate) fn end_all(&mut self) -> Option<Context> {
+        let main = self.threads[0].context.take();
+        self.threads.truncate(1);
+        self.threads[0].state = State::Ready;
+        self.current = 0;
+        self.remaining = self.time_slice;
+        main
+    }
+}
@@ -1,27 +1,75 @@
-// fixed capacity stack
+// heap allocated stack, growing up to a maximum size
+
+use alloc::{vec, vec::Vec};
 
 use crate::{ijvm::Frame, ijvm_core::InstructionRef};
 
-const STACK_SIZE: usize = 1 << 16;
+/// How many values the operand stack holds, set with
+/// [`crate::ijvm_core::Program::set_stack_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackSize {
+    /// Room allocated up front.
+    pub initial: usize,
+    /// Pushing past this many values traps with [`crate::trap::TrapKind::StackOverflow`].
+    /// The stack grows from `initial` up to it when needed.
+    pub max: usize,
+}
+
+impl StackSize {
+    /// A stack that never reallocates.
+    pub fn fixed(size: usize) -> StackSize {
+        StackSize {
+            initial: size,
+            max: size,
+        }
+    }
+
+    pub fn growable(initial: usize, max: usize) -> StackSize {
+        StackSize {
+            initial: initial.min(max),
+            max,
+        }
+    }
+}
+
+impl Default for StackSize {
+    fn default() -> Self {
+        StackSize::growable(1 << 10, 1 << 16)
+    }
+}
 
 #[derive(Debug)]
 pub struct Stack {
-    pub stack: [i32; STACK_SIZE],
+    // elements 1..=sp, element 0 is what an empty stack's top reads
+    pub stack: Vec<i32>,
     top_value: i32,
     sp: usize,
+    max: usize,
 }
 
 impl Stack {
     pub fn new() -> Stack {
+        Stack::with_size(StackSize::default())
+    }
+
+    pub fn with_size(size: StackSize) -> Stack {
         Stack {
-            stack: [0; STACK_SIZE],
+            stack: vec![0; size.initial + 1],
             top_value: 0,
             sp: 0,
+            max: size.max,
         }
     }
 
+    /// Pushes `value`, growing the stack if it is full. Returns false and leaves
+    /// the stack as it was once it has reached its maximum size.
     #[inline]
-    pub fn push(&mut self, value: i32) {
+    #[must_use]
+    pub fn push(&mut self, value: i32) -> bool {
+        if self.sp + 1 == self.stack.len() && !self.grow() {
+            return false;
+        }
+
         self.sp += 1;
         self.top_value = value;
 
@@ -35,6 +83,18 @@ impl Stack {
             self.stack[self.sp] = value;
         }
 
+        true
+    }
+
+    #[cold]
+    fn grow(&mut self) -> bool {
+        let size = self.stack.len() - 1;
+        if size >= self.max {
+            return false;
+        }
+        let new_size = (size * 2).max(16).min(self.max);
+        self.stack.resize(new_size + 1, 0);
+        true
     }
 
     #[inline]
@@ -74,24 +134,26 @@ impl Stack {
         }
     }
 
+    /// Pops the top `n` values and returns them, deepest first.
     #[inline]
     pub fn get_ref_top_n(&mut self, n: usize) -> &[i32] {
         if n == 0 {
             return &[];
         }
-        // we dont modify top value because this function is called on INVOKEVIRTUAL which always has empty stack
 
         self.sp -= n;
 
         #[cfg(feature = "unsafe")]
         unsafe {
+            self.top_value = *self.stack.get_unchecked(self.sp);
             // unchecked slice
             self.stack.get_unchecked(self.sp+1..self.sp + n+1)
         }
 
         #[cfg(not(feature = "unsafe"))]
         {
-            &self.stack[self.sp..self.sp + n]
+            self.top_value = self.stack[self.sp];
+            &self.stack[self.sp + 1..self.sp + n + 1]
         }
     }
 
@@ -105,8 +167,40 @@ impl Stack {
         self.sp 
     }
 
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sp == 0
+    }
+
     pub fn clear(&mut self) {
         self.sp = 0;
+        self.top_value = 0;
+    }
+
+    /// The live values on the stack, bottom first.
+    pub fn stack_slice(&self) -> &[i32] {
+        &self.stack[1..self.sp + 1]
+    }
+
+    /// Replaces the whole stack. `top_value` is kept as given, it is what TOS reads.
+    pub(crate) fn restore(&mut self, values: &[i32], top_value: i32) {
+        if self.stack.len() <= values.len() {
+            self.stack.resize(values.len() + 1, 0);
+        }
+        self.stack[1..values.len() + 1].copy_from_slice(values);
+        self.sp = values.len();
+        self.top_value = top_value;
+    }
+
+    /// How many values the stack can grow to.
+    pub fn max_size(&self) -> usize {
+        self.max
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -156,6 +250,20 @@ impl TinyVarsVec {
     pub fn reset(&mut self) {
         self.vars.clear();
     }
+
+    pub fn from_vec(vars: Vec<i32>) -> Self {
+        Self { vars }
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[i32] {
+        &self.vars
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [i32] {
+        &mut self.vars
+    }
 }
 
 pub struct TinyVarsDict {
@@ -191,22 +299,33 @@ impl TinyVarsDict {
     }
 }
 
+/// How many method frames can be on top of main unless set otherwise.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1 << 16;
+
 pub struct FrameStack {
     frames: Vec<Frame>,
+    main_var_count: u32,
+    max_depth: usize,
     // count: usize,
 }
 
 impl FrameStack {
-    pub fn new() -> FrameStack {
+    /// The main frame holds `main_var_count` locals, the binary doesn't say how many main uses.
+    pub fn new(main_var_count: u32) -> FrameStack {
         FrameStack {
-            frames: vec![Frame::new(0, 0, 0)],
+            frames: vec![Frame::new(0, main_var_count, 0, 0)],
+            main_var_count,
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
             // count: 1,
         }
     }
 
+    /// Pushes a frame for the method with its header at `method`, with `var_count`
+    /// locals, the first of which are set to `args` (OBJREF first).
     #[inline]
     pub fn push_frame(
         &mut self,
+        method: InstructionRef,
         starting_stack_length: u32,
         var_count: u32,
         restore_pc: InstructionRef,
@@ -216,17 +335,15 @@ impl FrameStack {
         //     self.frames[self.count].reset(starting_stack_length, var_count, restore_pc)
         // } else {
         self.frames
-            .push(Frame::new(starting_stack_length, var_count, restore_pc));
+            .push(Frame::new(starting_stack_length, var_count, restore_pc, method));
         // }
 
         // self.count += 1;
 
         let frame = self.current_frame();
 
-        frame.store_var(0, 0);
-
-        for i in 0..args.len() {
-            frame.store_var(i as u16, args[i]);
+        for (i, arg) in args.iter().enumerate() {
+            frame.store_var(i as u16, *arg);
         }
 
         frame
@@ -260,8 +377,67 @@ impl FrameStack {
         // &mut self.frames[self.count - 1]
     }
 
+    /// Number of method frames above the main frame.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    /// Where the current frame's values start on the stack.
+    #[cfg(feature = "checked")]
     #[inline]
+    pub(crate) fn base(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.starting_stack_length() as usize)
+    }
+
+    #[inline]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// How many locals the main frame starts with.
+    pub(crate) fn main_var_count(&self) -> u32 {
+        self.main_var_count
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Where the innermost `n` method frames were called from, innermost first.
+    pub fn backtrace(&self, n: usize) -> Vec<InstructionRef> {
+        self.frames[1..]
+            .iter()
+            .rev()
+            .take(n)
+            .map(|frame| frame.restore_pc())
+            .collect()
+    }
+
+    /// Drops the frames above `depth`.
+    pub(crate) fn truncate(&mut self, depth: usize) {
+        self.frames.truncate(depth + 1);
+    }
+
+    /// The frames, main first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Frame> + ExactSizeIterator {
+        self.frames.iter()
+    }
+
+    /// The frame `level` frames below the current one, or `None` past main.
+    pub fn get_mut(&mut self, level: usize) -> Option<&mut Frame> {
+        self.frames.iter_mut().rev().nth(level)
+    }
+
+    /// Replaces every frame, `frames` has to start with main's.
+    pub(crate) fn replace(&mut self, frames: Vec<Frame>) {
+        self.frames = frames;
+    }
+
+    /// Drops every method frame and zeroes main's locals.
     pub fn clear(&mut self) {
-        // self.count = 1;
+        self.frames.truncate(1);
+        self.frames[0] = Frame::new(0, self.main_var_count, 0, 0);
     }
 }
+
@@ -0,0 +1,102 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+use crate::{
+    ijvm_core::{InstructionRef, Outcome},
+    io::ErrorKind,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrapKind {
+    /// The program executed `ERR`.
+    Err,
+    /// An instruction popped more values than the stack holds.
+    StackUnderflow,
+    /// A push went past the maximum stack size, see [`crate::tiny::StackSize`].
+    StackOverflow,
+    /// `INVOKEVIRTUAL` targets something that is not a method, or a native method
+    /// that is already running.
+    BadMethod,
+    /// A branch offset points outside the text or into the middle of an instruction.
+    BadBranch,
+    /// `IRETURN` executed in the main frame.
+    ReturnFromMain,
+    /// The pc landed on bytes that are not an executable instruction.
+    InvalidOpcode,
+    /// Arithmetic overflow under [`crate::ijvm_core::OverflowMode::Checked`].
+    Overflow,
+    /// Division or remainder by zero in an extension instruction.
+    DivisionByZero,
+    /// Reading input for `IN` or writing output for `OUT` failed.
+    Io(ErrorKind),
+    /// `IN` reached the end of input under [`crate::ijvm_core::EofPolicy::Trap`].
+    EndOfInput,
+    /// `INVOKEVIRTUAL` went past the maximum call depth, see
+    /// [`crate::ijvm_core::Runtime::set_max_call_depth`].
+    CallDepthExceeded {
+        /// Call sites of the innermost frames, innermost first.
+        backtrace: Vec<InstructionRef>,
+    },
+    /// A method called with [`crate::ijvm_core::Runtime::invoke`] stopped without
+    /// returning or trapping, e.g. by executing `HALT`. Holds how it stopped.
+    NoReturn(Box<Outcome>),
+    /// A local variable the current frame doesn't have, found by the `checked` feature.
+    BadLocal,
+    /// `JOIN` named a thread that was never spawned.
+    BadThread,
+    /// Every thread is waiting in `JOIN`, or a thread joined itself.
+    Deadlock,
+}
+
+/// Why execution stopped abnormally, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// The faulting instruction, as an index into the instructions.
+    pub pc: InstructionRef,
+    /// Number of method frames above main at the time of the trap.
+    pub call_depth: usize,
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapKind::Err => write!(f, "ERR instruction"),
+            TrapKind::StackUnderflow => write!(f, "stack underflow"),
+            TrapKind::StackOverflow => write!(f, "stack overflow"),
+            TrapKind::BadMethod => write!(f, "INVOKEVIRTUAL target is not a method"),
+            TrapKind::BadBranch => write!(f, "branch target is not an instruction"),
+            TrapKind::ReturnFromMain => write!(f, "IRETURN outside of a method"),
+            TrapKind::InvalidOpcode => write!(f, "invalid opcode"),
+            TrapKind::Overflow => write!(f, "arithmetic overflow"),
+            TrapKind::DivisionByZero => write!(f, "division by zero"),
+            TrapKind::Io(kind) => write!(f, "I/O error: {}", kind),
+            TrapKind::EndOfInput => write!(f, "end of input"),
+            TrapKind::CallDepthExceeded { backtrace } => {
+                write!(f, "call depth limit exceeded, called from")?;
+                for pc in backtrace {
+                    write!(f, " {}", pc)?;
+                }
+                Ok(())
+            }
+            TrapKind::NoReturn(outcome) => {
+                write!(f, "method stopped without returning: {:?}", outcome)
+            }
+            TrapKind::BadLocal => write!(f, "local variable outside the frame"),
+            TrapKind::BadThread => write!(f, "JOIN of a thread that doesn't exist"),
+            TrapKind::Deadlock => write!(f, "every thread is waiting to join"),
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at instruction {} (call depth {})",
+            self.kind, self.pc, self.call_depth
+        )
+    }
+}
+
+impl core::error::Error for Trap {}
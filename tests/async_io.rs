@@ -0,0 +1,141 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_async_io {
+    use std::{
+        future::Future,
+        io,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use copp_rs::{
+        async_io::{AsyncInput, AsyncOutput},
+        ijvm_core::{init_ijvm, InputMode, Outcome},
+        trap::TrapKind,
+    };
+
+    /// Polls `future` to completion, returning its output and how often it was pending.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut pending = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, pending),
+                Poll::Pending => pending += 1,
+            }
+        }
+    }
+
+    /// Hands out one byte per read, and is pending before each.
+    struct Trickle(Vec<u8>);
+
+    struct PendingOnce(bool);
+
+    impl Future for PendingOnce {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    impl AsyncInput for Trickle {
+        async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            PendingOnce(false).await;
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0.remove(0);
+            Ok(1)
+        }
+    }
+
+    /// Takes what's written, then never finishes writing it.
+    struct Stalled(Vec<u8>);
+
+    impl AsyncOutput for Stalled {
+        async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.0.extend_from_slice(buf);
+            std::future::pending().await
+        }
+    }
+
+    struct Broken;
+
+    impl AsyncOutput for Broken {
+        async fn write_all(&mut self, _buf: &[u8]) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_echo_awaits_input() {
+        let mut runtime = init_ijvm("files/io/echo.ijvm").with_output(Vec::new());
+        let mut output = Vec::new();
+
+        let (outcome, pending) =
+            block_on(runtime.run_async(&mut Trickle(b"hello".to_vec()), &mut output));
+        assert_eq!(outcome, Outcome::Halted);
+        assert_eq!(output, b"hello");
+        // one wait per byte, and one for the end of input
+        assert_eq!(pending, 6);
+        assert_eq!(runtime.input_mode(), InputMode::Blocking);
+    }
+
+    #[test]
+    fn test_compute_yields() {
+        let mut runtime = init_ijvm("files/fuel/countdown.ijvm").with_output(Vec::new());
+        let mut output = Vec::new();
+
+        let (outcome, pending) = block_on(runtime.run_async(&mut &b""[..], &mut output));
+        assert_eq!(outcome, Outcome::Halted);
+        assert_eq!(output, b".");
+        assert_eq!(pending as u64, runtime.fuel_used() / (1 << 16));
+    }
+
+    #[test]
+    fn test_out_is_an_await_point() {
+        let mut runtime = init_ijvm("files/io/greet.ijvm").with_output(Vec::new());
+        let mut output = Stalled(Vec::new());
+        {
+            let mut input = &b""[..];
+            let mut future = pin!(runtime.run_async(&mut input, &mut output));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(output.0, b"h");
+        // BIPUSH and OUT, the countdown waits for the write
+        assert_eq!(runtime.fuel_used(), 2);
+    }
+
+    #[test]
+    fn test_dropped_future_restores_input_mode() {
+        let mut runtime = init_ijvm("files/io/echo.ijvm").with_output(Vec::new());
+        let mut output = Vec::new();
+        {
+            let mut input = Trickle(b"hello".to_vec());
+            let mut future = pin!(runtime.run_async(&mut input, &mut output));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(runtime.input_mode(), InputMode::Blocking);
+    }
+
+    #[test]
+    fn test_output_error_traps() {
+        let mut runtime = init_ijvm("files/conformance/out.ijvm").with_output(Vec::new());
+        let (outcome, _) = block_on(runtime.run_async(&mut &b""[..], &mut Broken));
+        match outcome {
+            Outcome::Trapped(trap) => {
+                assert_eq!(trap.kind, TrapKind::Io(io::ErrorKind::BrokenPipe))
+            }
+            outcome => panic!("expected a trap, got {:?}", outcome),
+        }
+    }
+}
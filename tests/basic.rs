@@ -1,127 +1,330 @@
+#![cfg(feature = "std")]
+
 #[cfg(test)]
 mod tests {
+    use compat::Ijvm;
     use copp_rs::*;
     use ijvm_core::init_ijvm;
 
-    // #[test]
-    // fn test_task1_1() {
-    //     /*
-    //     .constant
-    //     .end-constant
-
-    //     .main
-    //         BIPUSH 0x30
-    //         BIPUSH 0x31
-    //         IADD
-    //         OUT
-    //     HALT
-    //     .end-main
-    //      */
-    //     let runtime = init_ijvm("files/task1/program1.ijvm");
-    //     assert_eq!(runtime.constants().len(), 0);
-    //     assert_eq!(runtime.visit_instructions().len(), 5);
-    //     assert_eq!(
-    //         runtime.visit_instructions()[0],
-    //         instructions::MemoryBlock::BIPUSH(0x30)
-    //     );
-    //     assert_eq!(
-    //         runtime.visit_instructions()[1],
-    //         instructions::MemoryBlock::BIPUSH(0x31)
-    //     );
-    //     assert_eq!(
-    //         runtime.visit_instructions()[2],
-    //         instructions::MemoryBlock::IADD
-    //     );
-    //     assert_eq!(
-    //         runtime.visit_instructions()[3],
-    //         instructions::MemoryBlock::OUT
-    //     );
-    //     assert_eq!(
-    //         runtime.visit_instructions()[4],
-    //         instructions::MemoryBlock::HALT
-    //     );
-    // }
-
-    // #[test]
-    // fn test_task1_2() {
-    //     /*
-    //     .constant
-    //         piet 1
-    //         koos 2
-    //         jan 3
-    //     .end-constant
-
-    //     .main
-    //         NOP
-    //         LDC_W piet
-    //         DUP
-    //         LDC_W koos
-    //         IADD
-    //         LDC_W jan
-    //         IADD
-    //         OUT
-    //         NOP
-    //     HALT
-    //     .end-main
-    //      */
-    //     let runtime = init_ijvm("files/task1/program2.ijvm");
-    //     assert_eq!(runtime.constants().len(), 3);
-    //     assert_eq!(runtime.visit_instructions().len(), 10);
-
-    //     // only test for constants
-    //     assert_eq!(runtime.constants()[0], 1);
-    //     assert_eq!(runtime.constants()[1], 2);
-    //     assert_eq!(runtime.constants()[2], 3);
-    // }
-
-    // fn test_all(dir: &str) {
-    //     // test all .ijvm files in dir
-    //     let files = std::fs::read_dir(dir).unwrap();
-    //     for file in files {
-    //         let file = file.unwrap();
-    //         let path = file.path();
-    //         let path = path.to_str().unwrap();
-    //         if (!path.ends_with(".ijvm")) || path.split('/').last().unwrap().starts_with('_') {
-    //             continue;
-    //         }
-    //         println!("Testing file {}", path);
-    //         let mut runtime = init_ijvm(path);
-
-    //         while !runtime.is_finished() {
-    //             runtime.step();
-    //             println!(
-    //                 "{:?}  Stack: {:?}",
-    //                 runtime.inner.program_counter(),
-    //                 runtime.inner.visit_stack()
-    //             );
-    //         }
-    //     }
-    // }
-    // #[test]
-    // fn test_task2_all() {
-    //     test_all("files/task2");
-    // }
-
-    // #[test]
-    // fn test_task3_all() {
-    //     test_all("files/task3");
-    // }
-
-    // #[test]
-    // fn test_task4_all() {
-    //     test_all("files/task4");
-    // }
-
-    // #[test]
-    // fn test_task5_all() {
-    //     test_all("files/task5");
-    // }
-
-    // // advanced folder
-    // #[test]
-    // fn test_advanced_all() {
-    //     test_all("files/advanced");
-    // }
+    fn init(path: &str) -> Ijvm {
+        let mut ijvm = Ijvm::init(path).unwrap();
+        // the reference tests check tos(), not what ends up on stdout
+        ijvm.runtime().inner.out_stream = Box::new(std::io::sink());
+        ijvm
+    }
+
+    fn steps(ijvm: &mut Ijvm, count: usize) {
+        for _ in 0..count {
+            assert!(ijvm.step());
+        }
+    }
+
+    #[test]
+    fn test_task1_1() {
+        /*
+        .constant
+        .end-constant
+
+        .main
+            BIPUSH 0x30
+            BIPUSH 0x31
+            IADD
+            OUT
+        HALT
+        .end-main
+         */
+        let mut ijvm = init("files/task1/program1.ijvm");
+        assert_eq!(ijvm.get_text_size(), 7);
+        assert_eq!(ijvm.get_text(), [0x10, 0x30, 0x10, 0x31, 0x60, 0xFD, 0xFF]);
+        assert_eq!(ijvm.get_program_counter(), 0);
+        assert_eq!(ijvm.get_instruction(), 0x10);
+
+        // the text's byte, not the marker a breakpoint puts there
+        let pc = ijvm.runtime().program_counter();
+        ijvm.runtime().set_breakpoint(pc);
+        assert_eq!(ijvm.get_instruction(), 0x10);
+    }
+
+    #[test]
+    fn test_task1_2() {
+        /*
+        .constant
+            piet 1
+            koos 2
+            jan 3
+        .end-constant
+
+        .main
+            NOP
+            LDC_W piet
+            DUP
+            LDC_W koos
+            IADD
+            LDC_W jan
+            IADD
+            OUT
+            NOP
+        HALT
+        .end-main
+         */
+        let ijvm = init("files/task1/program2.ijvm");
+        assert_eq!(ijvm.get_constant(0), 1);
+        assert_eq!(ijvm.get_constant(1), 2);
+        assert_eq!(ijvm.get_constant(2), 3);
+        assert_eq!(ijvm.get_text_size(), 16);
+        assert_eq!(ijvm.get_text()[1..4], [0x13, 0x00, 0x00]);
+        assert_eq!(ijvm.get_text()[15], 0xFF);
+    }
+
+    #[test]
+    fn test_task2() {
+        let mut ijvm = init("files/task2/TestBipush1.ijvm");
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), 42);
+
+        let mut ijvm = init("files/task2/TestBipush2.ijvm");
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), -42);
+
+        let mut ijvm = init("files/task2/TestIadd1.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), 60);
+
+        let mut ijvm = init("files/task2/TestIadd2.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), -60);
+
+        let mut ijvm = init("files/task2/TestIsub1.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), -10);
+
+        let mut ijvm = init("files/task2/TestIsub2.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), 10);
+
+        let mut ijvm = init("files/task2/TestIAND1.ijvm");
+        steps(&mut ijvm, 4);
+        assert_eq!(ijvm.tos(), 5);
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), 1);
+
+        let mut ijvm = init("files/task2/TestIOR1.ijvm");
+        steps(&mut ijvm, 4);
+        assert_eq!(ijvm.tos(), 125);
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), 127);
+
+        let mut ijvm = init("files/task2/TestPop1.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), 10);
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), 50);
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), 10);
+
+        let mut ijvm = init("files/task2/TestSwap1.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.tos(), 10);
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.tos(), 20);
+    }
+
+    #[test]
+    fn test_task3_goto() {
+        let mut ijvm = init("files/task3/GOTO1.ijvm");
+        steps(&mut ijvm, 2);
+        assert_eq!(ijvm.get_program_counter(), 3);
+        steps(&mut ijvm, 1);
+        assert_eq!(ijvm.get_program_counter(), 9);
+        steps(&mut ijvm, 2);
+        assert_eq!(ijvm.get_program_counter(), 12);
+
+        let mut ijvm = init("files/task3/GOTO2.ijvm");
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.get_program_counter(), 10);
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.get_program_counter(), 6);
+        ijvm.run();
+        // past the HALT
+        assert_eq!(ijvm.get_program_counter(), 10);
+        assert!(ijvm.finished());
+    }
+
+    #[test]
+    fn test_task3_branches() {
+        let mut ijvm = init("files/task3/IFEQ1.ijvm");
+        ijvm.run();
+        // runs off the end of the text
+        assert!(ijvm.finished());
+        assert_eq!(ijvm.get_program_counter(), ijvm.get_text_size());
+
+        let mut ijvm = init("files/task3/IFICMPEQ1.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 0x13);
+
+        let mut ijvm = init("files/task3/IFLT1.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 0x37);
+    }
+
+    #[test]
+    fn test_task4() {
+        let mut ijvm = init("files/task4/LoadTest1.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 3);
+
+        let mut ijvm = init("files/task4/LoadTest2.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.get_local_variable(0), 3);
+
+        let mut ijvm = init("files/task4/LoadTest3.ijvm");
+        steps(&mut ijvm, 7);
+        assert_eq!(ijvm.get_local_variable(0), 1);
+        assert_eq!(ijvm.get_local_variable(1), 2);
+        assert_eq!(ijvm.get_local_variable(2), 3);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 42);
+
+        let mut ijvm = init("files/task4/LoadTest4.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.get_local_variable(0), 0);
+        assert_eq!(ijvm.get_local_variable(1), 97);
+        assert_eq!(ijvm.get_local_variable(4), 100);
+
+        let mut ijvm = init("files/task4/IINCTest.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.get_local_variable(0), 4);
+        assert_eq!(ijvm.get_local_variable(1), -4);
+    }
+
+    #[test]
+    fn test_task5_invoke() {
+        let mut ijvm = init("files/task5/TestInvokeArgs.ijvm");
+        steps(&mut ijvm, 4);
+        // past the method's 4 byte header
+        assert_eq!(ijvm.get_program_counter(), 14);
+        assert_eq!(ijvm.get_local_variable(1), 0x41);
+        assert_eq!(ijvm.get_local_variable(2), 0x42);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 0x83);
+
+        let mut ijvm = init("files/task5/TestInvokeNoArgs.ijvm");
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 0x43);
+
+        let mut ijvm = init("files/task5/test-invokevirtual1.ijvm");
+        steps(&mut ijvm, 6);
+        assert_eq!(ijvm.tos(), 1);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 2);
+
+        let mut ijvm = init("files/task5/test-invokevirtual2.ijvm");
+        steps(&mut ijvm, 10);
+        assert_eq!(ijvm.tos(), 5);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 2);
+    }
+
+    #[test]
+    fn test_task5_frames() {
+        let mut ijvm = init("files/task5/testinvoke-frame.ijvm");
+        steps(&mut ijvm, 7);
+        assert_eq!(ijvm.get_local_variable(0), 4);
+        assert_eq!(ijvm.get_local_variable(1), 3);
+        steps(&mut ijvm, 3);
+        assert_eq!(ijvm.get_local_variable(1), 2);
+        assert_eq!(ijvm.get_local_variable(2), 3);
+        steps(&mut ijvm, 4);
+        assert_eq!(ijvm.get_local_variable(0), 4);
+        assert_eq!(ijvm.get_local_variable(1), 3);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 2);
+
+        let mut ijvm = init("files/task5/test-nestedinvoke-frame.ijvm");
+        steps(&mut ijvm, 8);
+        assert_eq!(ijvm.get_local_variable(1), 1);
+        assert_eq!(ijvm.get_local_variable(2), 5);
+        // in addone
+        steps(&mut ijvm, 9);
+        assert_eq!(ijvm.get_local_variable(1), 9);
+        ijvm.run();
+        assert_eq!(ijvm.tos(), 0x10);
+        assert_eq!(ijvm.get_local_variable(0), 0x21);
+        assert_eq!(ijvm.get_local_variable(1), 0x2C);
+    }
+
+    #[test]
+    fn test_io_files() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("copp_rs-in-{}", std::process::id()));
+        let output = dir.join(format!("copp_rs-out-{}", std::process::id()));
+        std::fs::write(&input, b"echo").unwrap();
+
+        let mut ijvm = init("files/io/echo.ijvm");
+        ijvm.set_input(input.to_str().unwrap()).unwrap();
+        ijvm.set_output(output.to_str().unwrap()).unwrap();
+        ijvm.run();
+        assert_eq!(std::fs::read(&output).unwrap(), b"echo");
+
+        assert!(ijvm.set_input("files/missing").is_err());
+        assert!(Ijvm::init("files/missing.ijvm").is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    // the checked build loads these, and traps once it gets to the bad bytes
+    #[test]
+    #[cfg(not(feature = "checked"))]
+    fn test_undecodable_text() {
+        for path in ["files/checked/truncated.ijvm", "files/checked/badwide.ijvm"] {
+            let error = ijvm_core::Program::try_load(path).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+            assert!(Ijvm::init(path).is_err());
+        }
+    }
+
+    fn test_all(dir: &str) {
+        // test all .ijvm files in dir
+        let files = std::fs::read_dir(dir).unwrap();
+        for file in files {
+            let file = file.unwrap();
+            let path = file.path();
+            let path = path.to_str().unwrap();
+            if (!path.ends_with(".ijvm")) || file.file_name().to_string_lossy().starts_with('_') {
+                continue;
+            }
+            let mut runtime = init_ijvm(path)
+                .with_input(&b""[..])
+                .with_output(std::io::sink());
+            // some loop on the end of input, mandelbread takes long
+            runtime.run_with_fuel(1 << 20);
+        }
+    }
+    #[test]
+    fn test_task2_all() {
+        test_all("files/task2");
+    }
+
+    #[test]
+    fn test_task3_all() {
+        test_all("files/task3");
+    }
+
+    #[test]
+    fn test_task4_all() {
+        test_all("files/task4");
+    }
+
+    #[test]
+    fn test_task5_all() {
+        test_all("files/task5");
+    }
+
+    // advanced folder
+    #[test]
+    fn test_advanced_all() {
+        test_all("files/advanced");
+    }
 
     // run mandelbread.ijvm in advanced
     #[test]
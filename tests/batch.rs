@@ -0,0 +1,45 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_batch {
+    use copp_rs::{
+        batch::Batch,
+        ijvm_core::{Outcome, Program, Runtime},
+    };
+
+    #[test]
+    fn test_results_in_input_order() {
+        let program = Program::load("files/io/echo.ijvm");
+        let inputs = (0..50)
+            .map(|i| "x".repeat(i + 1).into_bytes())
+            .collect::<Vec<_>>();
+
+        let results = Batch::new(&program).with_threads(4).run(&inputs);
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.iter().zip(&results) {
+            assert_eq!(result.outcome, Outcome::Halted);
+            assert_eq!(&result.output, input);
+            // IN DUP IFEQ OUT GOTO per byte, then IN DUP IFEQ POP HALT
+            assert_eq!(result.instructions, 5 * input.len() as u64 + 5);
+        }
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let program = Program::load("files/fuel/loop.ijvm");
+        let results = Batch::new(&program)
+            .with_instruction_limit(100)
+            .run(&[b""; 3]);
+        for result in results {
+            assert_eq!(result.outcome, Outcome::BudgetExhausted);
+            assert_eq!(result.instructions, 100);
+        }
+    }
+
+    #[test]
+    fn test_runtime_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Runtime>();
+        assert_send::<Runtime<&[u8], Vec<u8>>>();
+    }
+}
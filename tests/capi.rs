@@ -0,0 +1,74 @@
+#![cfg(feature = "capi")]
+
+#[cfg(test)]
+mod tests_capi {
+    use std::{ffi::CString, sync::Mutex};
+
+    use copp_rs::capi::*;
+
+    // the C interface has one machine for the whole process
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn load(path: &str) -> i32 {
+        let path = CString::new(path).unwrap();
+        unsafe { init_ijvm(path.as_ptr()) }
+    }
+
+    #[test]
+    fn test_step_through_a_call() {
+        let _lock = LOCK.lock().unwrap();
+        assert_eq!(load("files/conformance/recursion.ijvm"), 0);
+        assert_eq!(get_program_counter(), 0);
+        assert_eq!(get_constant(0), 0);
+
+        // LDC_W, BIPUSH and INVOKEVIRTUAL, past the method's 4 byte header
+        for _ in 0..3 {
+            assert!(step());
+        }
+        assert_eq!(get_program_counter(), 13);
+        assert_eq!(get_instruction(), 0x15);
+        assert_eq!(get_local_variable(1), 10);
+        assert_eq!(get_local_variable(2), 0);
+        assert_eq!(get_local_variable(-1), 0);
+
+        run();
+        assert!(finished());
+        assert!(!step());
+        assert_eq!(tos(), 55);
+        let stack = unsafe { std::slice::from_raw_parts(get_stack(), stack_size() as usize) };
+        assert_eq!(stack, [55]);
+        destroy_ijvm();
+    }
+
+    #[test]
+    fn test_load_failures() {
+        let _lock = LOCK.lock().unwrap();
+        assert_eq!(load("files/task1/program2.ijvm"), 0);
+        assert_eq!(get_constant(2), 3);
+        assert_eq!(get_constant(3), 0);
+        let text = unsafe { std::slice::from_raw_parts(get_text(), get_text_size() as usize) };
+        assert_eq!(text.len(), 16);
+        assert_eq!(text[1..4], [0x13, 0x00, 0x00]);
+
+        // the loaded binary goes away with a failed load
+        assert_eq!(load("files/missing.ijvm"), -1);
+        #[cfg(not(feature = "checked"))]
+        assert_eq!(load("files/checked/badwide.ijvm"), -1);
+        assert!(finished());
+        assert_eq!(get_constant(2), 0);
+        assert_eq!(get_text_size(), 0);
+        assert_eq!(unsafe { init_ijvm(std::ptr::null()) }, -1);
+    }
+
+    /// Set `BLESS` to update the committed header instead.
+    #[test]
+    fn test_header_is_up_to_date() {
+        let generated = std::fs::read_to_string(env!("IJVM_H")).unwrap();
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write("include/ijvm.h", &generated).unwrap();
+            return;
+        }
+        let committed = std::fs::read_to_string("include/ijvm.h").unwrap();
+        assert_eq!(committed, generated);
+    }
+}
@@ -0,0 +1,57 @@
+#![cfg(all(feature = "std", feature = "checked"))]
+
+#[cfg(test)]
+mod tests_checked {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome},
+        trap::{Trap, TrapKind},
+    };
+
+    #[test]
+    fn test_bad_local() {
+        let mut runtime = init_ijvm("files/checked/badlocal.ijvm");
+        assert_eq!(
+            runtime.run(),
+            Outcome::Trapped(Trap {
+                kind: TrapKind::BadLocal,
+                pc: 6,
+                call_depth: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pop_below_frame() {
+        let mut runtime = init_ijvm("files/checked/underflow.ijvm");
+        assert_eq!(
+            runtime.run(),
+            Outcome::Trapped(Trap {
+                kind: TrapKind::StackUnderflow,
+                pc: 7,
+                call_depth: 1,
+            })
+        );
+        // main's values are still there
+        assert_eq!(runtime.stack_slice(), [7, 8, 9]);
+    }
+
+    #[test]
+    fn test_undecodable_bytes_trap() {
+        // loads, and runs up to the broken instruction
+        let mut runtime = init_ijvm("files/checked/truncated.ijvm").with_output(Vec::new());
+        assert_eq!(
+            runtime.run(),
+            Outcome::Trapped(Trap {
+                kind: TrapKind::InvalidOpcode,
+                pc: 2,
+                call_depth: 0,
+            })
+        );
+        assert_eq!(runtime.inner.out_stream, b"1");
+
+        let mut runtime = init_ijvm("files/checked/badwide.ijvm");
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::InvalidOpcode);
+        assert_eq!(runtime.tos(), 0x31);
+    }
+}
@@ -0,0 +1,110 @@
+// Opcode-by-opcode checks against the IJVM spec, using the small programs in
+// files/conformance. Each .ijvm there is assembled from the .jas next to it.
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_conformance {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome, Runtime},
+        instructions::{MemoryBlock, WideMemoryBlock},
+    };
+
+    fn stack(runtime: &Runtime) -> Vec<i32> {
+        let stack = runtime.inner.visit_stack();
+        stack.stack[1..stack.len() + 1].to_vec()
+    }
+
+    fn run(file: &str) -> Runtime {
+        let mut runtime = init_ijvm(file);
+        assert_eq!(runtime.run(), Outcome::Halted);
+        runtime
+    }
+
+    #[test]
+    fn test_bipush_sign_extends() {
+        let runtime = run("files/conformance/bipush.ijvm");
+        assert_eq!(stack(&runtime), vec![127, -1, -128, 0]);
+    }
+
+    #[test]
+    fn test_ldc_w() {
+        let runtime = run("files/conformance/ldc_w.ijvm");
+        assert_eq!(stack(&runtime), vec![i32::MAX, -1, i32::MIN]);
+    }
+
+    #[test]
+    fn test_stack_ops() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.steps(3);
+        assert_eq!(stack(&runtime), vec![2, 1]);
+        runtime.step();
+        assert_eq!(stack(&runtime), vec![2, 1, 1]);
+        runtime.run();
+        assert_eq!(stack(&runtime), vec![2, 2]);
+        assert_eq!(runtime.tos(), 2);
+    }
+
+    #[test]
+    fn test_arith_and_logic() {
+        let runtime = run("files/conformance/arith.ijvm");
+        assert_eq!(stack(&runtime), vec![-9, 8, 14, 5, -127]);
+    }
+
+    #[test]
+    fn test_iinc_is_signed() {
+        let runtime = run("files/conformance/iinc.ijvm");
+        assert_eq!(runtime.visit_instructions()[2], MemoryBlock::IINC(0, -1));
+        assert_eq!(stack(&runtime), vec![9, -119, 8, 1]);
+    }
+
+    #[test]
+    fn test_wide() {
+        let runtime = run("files/conformance/wide.ijvm");
+        assert_eq!(
+            runtime.visit_instructions()[4],
+            MemoryBlock::WIDE(WideMemoryBlock::IIINC(0x100, -2))
+        );
+        assert_eq!(stack(&runtime), vec![40, 7, 7, 134]);
+    }
+
+    #[test]
+    fn test_branches() {
+        let runtime = run("files/conformance/branches.ijvm");
+        assert_eq!(stack(&runtime), vec![3]);
+    }
+
+    #[test]
+    fn test_invoke_objref_and_args() {
+        let mut runtime = init_ijvm("files/conformance/invoke.ijvm");
+
+        // inside f, after its first instruction
+        runtime.steps(6);
+        assert_eq!(runtime.frame().load_var(0), 0x2A);
+        assert_eq!(runtime.frame().load_var(1), 5);
+        assert_eq!(runtime.frame().load_var(2), 7);
+        assert_eq!(runtime.frame().load_var(3), 0);
+        assert_eq!(stack(&runtime), vec![0x11, 99]);
+
+        // the return value replaces OBJREF, leftovers of f are dropped
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(stack(&runtime), vec![0x11, 40]);
+    }
+
+    #[test]
+    fn test_branch_at_method_boundary() {
+        let runtime = run("files/conformance/method_boundary.ijvm");
+        assert_eq!(stack(&runtime), vec![3]);
+    }
+
+    #[test]
+    fn test_recursion() {
+        let runtime = run("files/conformance/recursion.ijvm");
+        assert_eq!(stack(&runtime), vec![55]);
+    }
+
+    #[test]
+    fn test_out_pops() {
+        let runtime = run("files/conformance/out.ijvm");
+        assert_eq!(stack(&runtime), vec![0x43]);
+    }
+}
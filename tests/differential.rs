@@ -0,0 +1,83 @@
+// the extensions change what some of the corpus means
+#![cfg(not(any(feature = "ext-arith", feature = "green-threads")))]
+
+#[cfg(test)]
+mod tests_differential {
+    use std::{fmt::Write, path::Path};
+
+    use copp_rs::ijvm_core::Program;
+
+    // how every program in files/ behaves, which every build has to agree on
+    const EXPECTED: &str = "files/differential.tsv";
+    const INPUT: &[u8] = b"99 5 + 4 / 22 1*- ! ?.\n";
+    const INSTRUCTION_LIMIT: u64 = 1 << 20;
+
+    fn corpus(dir: &Path, programs: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            // what only the checked build can run safely
+            if path.ends_with("files/checked") {
+                continue;
+            }
+            if path.is_dir() {
+                corpus(&path, programs);
+            } else if path.extension().is_some_and(|ext| ext == "ijvm") {
+                programs.push(path.to_str().unwrap().to_string());
+            }
+        }
+    }
+
+    /// One line of the expectations: outcome, instructions executed, top of stack,
+    /// and the output's length and FNV-1a hash.
+    fn behavior(path: &str) -> String {
+        // read here, so it runs without std too
+        let binary = std::fs::read(path).unwrap();
+        let Ok(program) = Program::from_bytes(&binary) else {
+            return format!("{}\tfails to load", path);
+        };
+        let mut runtime = program.runtime().with_input(INPUT).with_output(Vec::new());
+        let outcome = runtime.run_with_fuel(INSTRUCTION_LIMIT);
+
+        let output = &runtime.inner.out_stream;
+        let hash = output.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!(
+            "{}\t{:?}\t{}\t{}\t{}\t{:016x}",
+            path,
+            outcome,
+            runtime.fuel_used(),
+            runtime.tos(),
+            output.len(),
+            hash
+        )
+    }
+
+    /// Set `BLESS` to write the expectations from this build instead.
+    #[test]
+    fn test_corpus_behaves_the_same() {
+        let mut programs = Vec::new();
+        corpus(Path::new("files"), &mut programs);
+        programs.sort();
+
+        let mut actual = String::new();
+        for program in &programs {
+            writeln!(actual, "{}", behavior(program)).unwrap();
+        }
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(EXPECTED, &actual).unwrap();
+            return;
+        }
+
+        let expected = std::fs::read_to_string(EXPECTED).unwrap();
+        for (expected, actual) in expected.lines().zip(actual.lines()) {
+            // the checked build loads them, and traps instead
+            if cfg!(feature = "checked") && expected.ends_with("\tfails to load") {
+                assert!(actual.contains("\tTrapped("), "{}", actual);
+                continue;
+            }
+            assert_eq!(actual, expected);
+        }
+        assert_eq!(actual.lines().count(), expected.lines().count());
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(all(feature = "std", feature = "ext-arith"))]
+
+#[cfg(test)]
+mod tests_ext_arith {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome},
+        instructions::MemoryBlock,
+        trap::TrapKind,
+    };
+
+    #[test]
+    fn test_parse() {
+        let runtime = init_ijvm("files/ext/arith.ijvm");
+        assert_eq!(runtime.visit_instructions()[2], MemoryBlock::IMUL);
+        assert_eq!(runtime.visit_instructions()[4], MemoryBlock::IDIV);
+        assert_eq!(runtime.visit_instructions()[6], MemoryBlock::IREM);
+        assert_eq!(runtime.visit_instructions()[8], MemoryBlock::ISHL);
+        assert_eq!(runtime.visit_instructions()[9], MemoryBlock::INEG);
+        assert_eq!(runtime.visit_instructions()[11], MemoryBlock::ISHR);
+        assert_eq!(runtime.visit_instructions()[13], MemoryBlock::IUSHR);
+        assert_eq!(runtime.visit_instructions()[15], MemoryBlock::IXOR);
+    }
+
+    #[test]
+    fn test_arith() {
+        let mut runtime = init_ijvm("files/ext/arith.ijvm");
+
+        runtime.steps(3);
+        assert_eq!(runtime.tos(), 21);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), 5);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), 2);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), 16);
+        runtime.step();
+        assert_eq!(runtime.tos(), -16);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), -4);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), 15);
+        runtime.steps(2);
+        assert_eq!(runtime.tos(), 10);
+        assert_eq!(runtime.run(), Outcome::Halted);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut runtime = init_ijvm("files/ext/divzero.ijvm");
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::DivisionByZero);
+        assert_eq!(runtime.trap().unwrap().pc, 2);
+    }
+}
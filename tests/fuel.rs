@@ -0,0 +1,95 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_fuel {
+    use copp_rs::{
+        fuel::CostTable,
+        ijvm_core::{init_ijvm, Outcome},
+    };
+
+    #[test]
+    fn test_infinite_loop_runs_out() {
+        let mut runtime = init_ijvm("files/fuel/loop.ijvm");
+
+        assert_eq!(runtime.run_with_fuel(100), Outcome::BudgetExhausted);
+        assert_eq!(runtime.fuel_used(), 100);
+        assert_eq!(runtime.program_counter(), 1);
+
+        // resumable
+        assert_eq!(runtime.run_with_fuel(50), Outcome::BudgetExhausted);
+        assert_eq!(runtime.fuel_used(), 150);
+        assert_eq!(runtime.steps(1), Outcome::Running);
+    }
+
+    #[test]
+    fn test_uniform_counts_instructions() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Halted);
+        assert_eq!(runtime.fuel_used(), 10);
+    }
+
+    #[test]
+    fn test_weighted_costs() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_cost_table(CostTable::uniform().with_cost(0x59, 5));
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Halted);
+        assert_eq!(runtime.fuel_used(), 18);
+
+        // stops before an instruction it can't pay for, and picks up after
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_cost_table(CostTable::uniform().with_cost(0x59, 5));
+        assert_eq!(runtime.run_with_fuel(4), Outcome::BudgetExhausted);
+        assert_eq!(runtime.program_counter(), 3);
+        assert_eq!(runtime.fuel_used(), 3);
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Halted);
+        assert_eq!(runtime.tos(), 2);
+    }
+
+    #[test]
+    fn test_breakpoint_instruction_is_charged() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_cost_table(CostTable::uniform().with_cost(0x59, 5));
+        runtime.set_breakpoint(3);
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Breakpoint);
+        assert_eq!(runtime.fuel_used(), 3);
+
+        // the DUP under it needs 5, and executes once it gets them
+        assert_eq!(runtime.run_with_fuel(4), Outcome::BudgetExhausted);
+        assert_eq!(runtime.fuel_used(), 3);
+        assert_eq!(runtime.program_counter(), 3);
+        assert_eq!(runtime.run_with_fuel(5), Outcome::BudgetExhausted);
+        assert_eq!(runtime.fuel_used(), 8);
+        assert_eq!(runtime.program_counter(), 4);
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Halted);
+        assert_eq!(runtime.fuel_used(), 18);
+    }
+
+    #[test]
+    fn test_breakpoint_costs_its_instruction() {
+        // SWAP leaves 1 fuel, the DUP under the breakpoint needs 5
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_cost_table(CostTable::uniform().with_cost(0x59, 5));
+        runtime.set_breakpoint(3);
+        assert_eq!(runtime.run_with_fuel(4), Outcome::BudgetExhausted);
+        assert_eq!(runtime.program_counter(), 3);
+        assert_eq!(runtime.run_with_fuel(1000), Outcome::Breakpoint);
+        assert_eq!(runtime.fuel_used(), 3);
+
+        // NOP being dear doesn't make the breakpoint's DUP dear
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_cost_table(CostTable::uniform().with_cost(0x00, 10));
+        runtime.set_breakpoint(3);
+        assert_eq!(runtime.run_with_fuel(4), Outcome::Breakpoint);
+        assert_eq!(runtime.run_with_fuel(1), Outcome::BudgetExhausted);
+        assert_eq!(runtime.program_counter(), 4);
+        assert_eq!(runtime.fuel_used(), 4);
+    }
+
+    #[test]
+    fn test_reset_clears_fuel_used() {
+        let mut runtime = init_ijvm("files/fuel/loop.ijvm");
+        runtime.run_with_fuel(10);
+        runtime.reset();
+        assert_eq!(runtime.fuel_used(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_input {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, EofPolicy, InputMode, Outcome},
+        trap::TrapKind,
+    };
+
+    #[test]
+    fn test_non_blocking_pauses_on_in() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+        assert_eq!(runtime.program_counter(), 0);
+
+        runtime.feed_input(b"a");
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+        assert_eq!(runtime.program_counter(), 1);
+        assert_eq!(runtime.tos(), b'a' as i32);
+
+        runtime.feed_input(b"b");
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), b'b' as i32);
+    }
+
+    #[test]
+    fn test_step_pauses_on_in() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+
+        assert_eq!(runtime.step(), Outcome::NeedsInput);
+        assert_eq!(runtime.step(), Outcome::NeedsInput);
+        runtime.feed_input(b"xy");
+        assert_eq!(runtime.step(), Outcome::Running);
+        assert_eq!(runtime.step(), Outcome::Running);
+        assert_eq!(runtime.tos(), b'y' as i32);
+        assert_eq!(runtime.step(), Outcome::Halted);
+    }
+
+    #[test]
+    fn test_eof_policies() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.feed_input(b"a");
+        runtime.close_input();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 0);
+
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.set_eof_policy(EofPolicy::PushMinusOne);
+        runtime.close_input();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), -1);
+
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.set_eof_policy(EofPolicy::Trap);
+        runtime.feed_input(b"a");
+        runtime.close_input();
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::EndOfInput);
+        assert_eq!(runtime.trap().unwrap().pc, 1);
+        assert_eq!(runtime.program_counter(), 1);
+        assert_eq!(runtime.tos(), b'a' as i32);
+    }
+
+    #[test]
+    fn test_custom_streams() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm").with_input(&b"xy"[..]);
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), b'y' as i32);
+
+        let mut runtime = init_ijvm("files/conformance/out.ijvm").with_output(Vec::new());
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream(), b"BA");
+    }
+
+    #[test]
+    fn test_fed_input_comes_before_stream() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm").with_input(&b"y"[..]);
+        runtime.feed_input(b"x");
+        runtime.run();
+        assert_eq!(runtime.tos(), b'y' as i32);
+        runtime.inner.stack_pop();
+        assert_eq!(runtime.tos(), b'x' as i32);
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_interrupt {
+    use std::{thread, time::Duration};
+
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome},
+        trap::TrapKind,
+    };
+
+    #[test]
+    fn test_interrupt_from_another_thread() {
+        let mut runtime = init_ijvm("files/fuel/loop.ijvm");
+        let handle = runtime.interrupt_handle();
+
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+        assert_eq!(runtime.run(), Outcome::Interrupted);
+        interrupter.join().unwrap();
+
+        // stopped right after the backward GOTO, and resumable
+        assert_eq!(runtime.program_counter(), 0);
+        assert_eq!(runtime.inner.stack_len(), 0);
+        assert_eq!(runtime.run_with_fuel(30), Outcome::BudgetExhausted);
+        assert_eq!(runtime.program_counter(), 0);
+    }
+
+    #[test]
+    fn test_pending_interrupt_stops_at_call() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.interrupt_handle().interrupt();
+
+        assert_eq!(runtime.run(), Outcome::Interrupted);
+        assert_eq!(runtime.inner.frames().depth(), 1);
+
+        // the request is consumed
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 55);
+    }
+
+    #[test]
+    fn test_interrupt_does_not_hide_trap() {
+        let mut runtime = init_ijvm("files/traps/ifeq_underflow.ijvm");
+        runtime.interrupt_handle().interrupt();
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackUnderflow);
+        assert_eq!(runtime.trap().unwrap().pc, 1);
+        // the IFEQ didn't branch
+        assert_eq!(runtime.program_counter(), 1);
+    }
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_introspection {
+    use copp_rs::{
+        ijvm::FrameInfo,
+        ijvm_core::{init_ijvm, Outcome},
+        snapshot::Snapshot,
+    };
+
+    // the header of recursion.jas's sum, and the recursive call in it
+    const SUM: usize = 4;
+    const RECURSIVE_CALL: usize = 11;
+
+    #[test]
+    fn test_call_stack() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+
+        let frames = runtime.call_stack().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 7);
+        assert_eq!(
+            frames[0],
+            FrameInfo {
+                method: Some(SUM),
+                return_pc: RECURSIVE_CALL,
+                base: 0,
+                locals: &[0, 5],
+            }
+        );
+        // called from main
+        assert_eq!(frames[5].method, Some(SUM));
+        assert_eq!(frames[5].return_pc, 2);
+        assert_eq!(frames[5].locals, [0, 10]);
+        assert_eq!(frames[6].method, None);
+    }
+
+    #[test]
+    fn test_write_locals() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+
+        runtime.locals_mut(0).unwrap()[1] = 1;
+        assert!(runtime.locals_mut(7).is_none());
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 10 + 9 + 8 + 7 + 6 + 1);
+    }
+
+    #[test]
+    fn test_stack_slice() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        assert_eq!(runtime.stack_slice(), []);
+        runtime.steps(2);
+        assert_eq!(runtime.stack_slice(), [0, 10]);
+        runtime.run();
+        assert_eq!(runtime.stack_slice(), [55]);
+    }
+
+    #[test]
+    fn test_version_1_snapshot() {
+        // 40 steps into recursion.ijvm, from before frames knew their method
+        let bytes = std::fs::read("files/snapshot/recursion-v1.snap").unwrap();
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.restore(&snapshot).unwrap();
+        let mut live = init_ijvm("files/conformance/recursion.ijvm");
+        live.steps(40);
+        assert!(runtime.call_stack().eq(live.call_stack()));
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 55);
+    }
+}
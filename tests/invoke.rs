@@ -0,0 +1,111 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_invoke {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, MethodId, Outcome},
+        instructions::MemoryBlock,
+        trap::{Trap, TrapKind},
+    };
+
+    #[test]
+    fn test_invoke_by_constant_offset_and_symbol() {
+        let mut runtime = init_ijvm("files/invoke/lib.ijvm");
+        assert_eq!(runtime.invoke(MethodId::Constant(1), &[2, 3]), Ok(5));
+        assert_eq!(runtime.invoke(MethodId::Offset(0x23), &[10]), Ok(55));
+
+        runtime.add_symbol("sum", 0x23);
+        assert_eq!(runtime.invoke(MethodId::Symbol("sum"), &[100]), Ok(5050));
+    }
+
+    #[test]
+    fn test_invoke_under_breakpoints() {
+        let mut runtime = init_ijvm("files/invoke/lib.ijvm");
+        let header = runtime
+            .visit_instructions()
+            .iter()
+            .position(|instruction| matches!(instruction, MemoryBlock::METHODHEADER { .. }))
+            .unwrap();
+        // on add's header and its first instruction
+        runtime.set_breakpoint(header);
+        runtime.set_breakpoint(header + 1);
+        assert_eq!(runtime.invoke(MethodId::Constant(1), &[2, 3]), Ok(5));
+    }
+
+    #[test]
+    fn test_caller_state_untouched() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+        let before = runtime.snapshot();
+
+        // the method recursion.jas is in the middle of
+        let result = runtime.invoke(MethodId::Constant(1), &[4]);
+        assert_eq!(result, Ok(10));
+        assert_eq!(runtime.snapshot(), before);
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 55);
+
+        // a method that pops the caller's values puts nothing back itself, and
+        // the checked build doesn't let it
+        let mut runtime = init_ijvm("files/checked/underflow.ijvm");
+        runtime.steps(3);
+        let before = runtime.snapshot();
+        let result = runtime.invoke(MethodId::Constant(1), &[]);
+        if cfg!(feature = "checked") {
+            assert_eq!(result.unwrap_err().kind, TrapKind::StackUnderflow);
+        } else {
+            assert_eq!(result, Ok(1));
+        }
+        assert_eq!(runtime.snapshot(), before);
+        assert_eq!(runtime.stack_slice(), [7, 8, 9]);
+
+        // an interrupt requested before the call is left for the caller
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+        runtime.interrupt_handle().interrupt();
+        assert_eq!(runtime.invoke(MethodId::Constant(1), &[4]), Ok(10));
+        assert_eq!(runtime.run(), Outcome::Interrupted);
+    }
+
+    #[test]
+    fn test_invoke_failures() {
+        let mut runtime = init_ijvm("files/invoke/lib.ijvm");
+        let before = runtime.snapshot();
+
+        let bad = |result: Result<i32, Trap>| result.unwrap_err().kind;
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Constant(0), &[])),
+            TrapKind::BadMethod
+        );
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Constant(9), &[])),
+            TrapKind::BadMethod
+        );
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Offset(0x1A), &[])),
+            TrapKind::BadMethod
+        );
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Symbol("add"), &[])),
+            TrapKind::BadMethod
+        );
+        // wrong number of arguments
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Constant(1), &[1])),
+            TrapKind::BadMethod
+        );
+
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Constant(4), &[])),
+            TrapKind::Err
+        );
+        assert_eq!(
+            bad(runtime.invoke(MethodId::Constant(3), &[])),
+            TrapKind::NoReturn(Box::new(Outcome::Halted))
+        );
+
+        assert_eq!(runtime.snapshot(), before);
+        assert_eq!(runtime.invoke(MethodId::Constant(1), &[-1, 1]), Ok(0));
+    }
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_native {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, MethodId, Outcome, Runtime},
+        instructions::MemoryBlock,
+        trap::TrapKind,
+    };
+
+    fn stack(runtime: &Runtime) -> Vec<i32> {
+        runtime.inner.visit_stack().stack_slice().to_vec()
+    }
+
+    #[test]
+    fn test_natives_replace_stubs() {
+        let mut runtime = init_ijvm("files/native/natives.ijvm");
+        assert!(runtime.register_native(MethodId::Constant(1), |_, args| args[0] * 2));
+
+        let mut ticks = 0;
+        runtime.add_symbol("clock", 0x1E);
+        assert!(
+            runtime.register_native(MethodId::Symbol("clock"), move |_, args| {
+                assert!(args.is_empty());
+                ticks += 7;
+                ticks
+            })
+        );
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(stack(&runtime), vec![40, 7, 3]);
+
+        // natives keep their state across runs
+        runtime.reset();
+        runtime.run();
+        assert_eq!(stack(&runtime), vec![40, 14, 3]);
+
+        assert_eq!(runtime.invoke(MethodId::Constant(1), &[5]), Ok(10));
+        assert!(!runtime.register_native(MethodId::Offset(1), |_, _| 0));
+    }
+
+    #[test]
+    fn test_unregistered_stub_traps() {
+        let mut runtime = init_ijvm("files/native/natives.ijvm");
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::Err);
+    }
+
+    #[test]
+    fn test_native_uses_runtime() {
+        let mut runtime = init_ijvm("files/native/natives.ijvm").with_output(Vec::new());
+        runtime.register_native(MethodId::Constant(1), |runtime, args| {
+            runtime.write_output(args[0] as u8);
+            0
+        });
+        runtime.register_native(MethodId::Constant(2), |runtime, _| {
+            runtime.trap(TrapKind::Err);
+            0
+        });
+
+        runtime.run();
+        assert_eq!(runtime.inner.out_stream, vec![20]);
+        // the trap points at the call, which returned nothing
+        assert_eq!(runtime.trap().unwrap().pc, 4);
+        assert_eq!(runtime.inner.visit_stack().stack_slice(), [0]);
+    }
+
+    #[test]
+    fn test_native_calling_itself_traps() {
+        let mut runtime = init_ijvm("files/native/natives.ijvm");
+        runtime.register_native(MethodId::Constant(1), |_, _| 0);
+        let header = runtime
+            .visit_instructions()
+            .iter()
+            .position(|instruction| matches!(instruction, MemoryBlock::NATIVE { .. }))
+            .unwrap();
+        runtime.register_native(MethodId::Constant(1), move |runtime, args| {
+            runtime.stack_push(0x40);
+            runtime.stack_push(args[0]);
+            MemoryBlock::RESOLVED_INVOKEVIRTUAL(header).execute(runtime);
+            0
+        });
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::BadMethod);
+        assert_eq!(runtime.trap().unwrap().pc, 2);
+    }
+
+    #[test]
+    fn test_changing_streams_drops_natives() {
+        let mut runtime = init_ijvm("files/native/natives.ijvm");
+        runtime.register_native(MethodId::Constant(1), |_, args| args[0] * 2);
+        let mut runtime = runtime.with_output(Vec::new());
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::Err);
+    }
+}
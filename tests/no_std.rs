@@ -0,0 +1,73 @@
+#![cfg(not(feature = "std"))]
+
+#[cfg(test)]
+mod tests_no_std {
+    use copp_rs::{
+        ijvm_core::{Outcome, Program},
+        io::{self, ErrorKind},
+        trap::TrapKind,
+    };
+
+    // the library can't read files, the test can
+    fn load(path: &str) -> Program {
+        Program::from_bytes(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    struct Failing(ErrorKind);
+
+    impl io::Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(self.0.into())
+        }
+    }
+
+    #[test]
+    fn test_default_streams() {
+        let mut runtime = load("files/task1/program1.ijvm").runtime();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream, b"a");
+
+        // nothing to read
+        let mut runtime = load("files/io/echo.ijvm").runtime();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert!(runtime.inner.out_stream.is_empty());
+
+        let mut runtime = load("files/io/echo.ijvm").runtime().with_input(&b"hi"[..]);
+        runtime.run();
+        assert_eq!(runtime.inner.out_stream, b"hi");
+    }
+
+    #[test]
+    fn test_input_errors() {
+        let program = load("files/io/echo.ijvm");
+        let mut runtime = program.runtime().with_input(Failing(ErrorKind::BrokenPipe));
+        runtime.run();
+        assert_eq!(
+            runtime.trap().unwrap().kind,
+            TrapKind::Io(ErrorKind::BrokenPipe)
+        );
+
+        let mut runtime = program.runtime().with_input(Failing(ErrorKind::WouldBlock));
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+    }
+
+    #[test]
+    fn test_bad_binaries() {
+        let error = Program::from_bytes(b"\x1d\xea").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        let error = Program::from_bytes(b"\x1d\xea\xdf\xae").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        // the pool claims 8 bytes, there are 2
+        let error = Program::from_bytes(b"\x1d\xea\xdf\xad\0\0\0\0\0\0\0\x08\0\0")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        // BIPUSH without its operand
+        #[cfg(not(feature = "checked"))]
+        {
+            let binary = b"\x1d\xea\xdf\xad\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\x10";
+            let error = Program::from_bytes(binary).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
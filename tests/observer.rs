@@ -0,0 +1,135 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_observer {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, InstructionRef, MethodId, Outcome},
+        instructions::MemoryBlock,
+        observer::Observer,
+        trap::{Trap, TrapKind},
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Call(InstructionRef, InstructionRef),
+        Return(InstructionRef, InstructionRef, i32),
+        Branch(InstructionRef, InstructionRef),
+        Input(Option<u8>),
+        Output(u8),
+        Trap(TrapKind),
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        executed: Vec<InstructionRef>,
+        unfinished: usize,
+        events: Vec<Event>,
+    }
+
+    impl Observer for Recorder {
+        fn before_instruction(&mut self, pc: InstructionRef, _instruction: &MemoryBlock) {
+            self.executed.push(pc);
+            self.unfinished += 1;
+        }
+        fn after_instruction(&mut self, _pc: InstructionRef, _instruction: &MemoryBlock) {
+            self.unfinished -= 1;
+        }
+        fn on_call(&mut self, call_site: InstructionRef, method: InstructionRef) {
+            self.events.push(Event::Call(call_site, method));
+        }
+        fn on_return(&mut self, from: InstructionRef, to: InstructionRef, value: i32) {
+            self.events.push(Event::Return(from, to, value));
+        }
+        fn on_branch(&mut self, from: InstructionRef, to: InstructionRef) {
+            self.events.push(Event::Branch(from, to));
+        }
+        fn on_input(&mut self, byte: Option<u8>) {
+            self.events.push(Event::Input(byte));
+        }
+        fn on_output(&mut self, byte: u8) {
+            self.events.push(Event::Output(byte));
+        }
+        fn on_trap(&mut self, trap: &Trap) {
+            self.events.push(Event::Trap(trap.kind.clone()));
+        }
+    }
+
+    #[test]
+    fn test_calls_and_instructions() {
+        let mut runtime =
+            init_ijvm("files/conformance/invoke.ijvm").with_observer(Recorder::default());
+        assert_eq!(runtime.run(), Outcome::Halted);
+
+        let recorder = runtime.observer();
+        assert_eq!(
+            recorder.executed,
+            vec![0, 1, 2, 3, 4, 7, 8, 9, 10, 11, 12, 13, 14, 15, 5]
+        );
+        assert_eq!(recorder.unfinished, 0);
+        assert_eq!(
+            recorder.events,
+            vec![Event::Call(4, 6), Event::Return(15, 4, 40)]
+        );
+    }
+
+    #[test]
+    fn test_native_calls() {
+        let mut runtime =
+            init_ijvm("files/native/natives.ijvm").with_observer(Recorder::default());
+        runtime.register_native(MethodId::Constant(1), |_, args| args[0] * 2);
+        runtime.register_native(MethodId::Constant(2), |runtime, _| {
+            runtime.trap(TrapKind::Err);
+            0
+        });
+        runtime.run();
+
+        // the one that trapped doesn't return
+        assert_eq!(
+            runtime.observer().events,
+            vec![
+                Event::Call(2, 10),
+                Event::Return(10, 2, 40),
+                Event::Call(4, 12),
+                Event::Trap(TrapKind::Err),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branches_taken() {
+        let mut runtime =
+            init_ijvm("files/conformance/branches.ijvm").with_observer(Recorder::default());
+        assert_eq!(runtime.run(), Outcome::Halted);
+
+        let branches = &runtime.observer().events;
+        assert_eq!(branches[..2], [Event::Branch(4, 0), Event::Branch(4, 0)]);
+        assert_eq!(branches[2], Event::Branch(3, 5));
+        assert_eq!(branches.len(), 6);
+    }
+
+    #[test]
+    fn test_io_and_traps() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm")
+            .with_input(&b"x"[..])
+            .with_output(Vec::new())
+            .with_observer(Recorder::default());
+        runtime.run();
+        assert_eq!(
+            runtime.observer().events,
+            vec![Event::Input(Some(b'x')), Event::Input(None)]
+        );
+
+        let mut runtime = init_ijvm("files/conformance/out.ijvm")
+            .with_output(Vec::new())
+            .with_observer(Recorder::default());
+        runtime.run();
+        assert_eq!(
+            runtime.observer().events,
+            vec![Event::Output(0x42), Event::Output(0x41)]
+        );
+
+        let mut runtime = init_ijvm("files/traps/err.ijvm").with_observer(Recorder::default());
+        runtime.run();
+        assert_eq!(runtime.observer().events, vec![Event::Trap(TrapKind::Err)]);
+    }
+}
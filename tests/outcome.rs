@@ -0,0 +1,75 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_outcome {
+    use copp_rs::ijvm_core::{init_ijvm, InputMode, Outcome};
+
+    #[test]
+    fn test_halt_and_end_of_program() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        assert_eq!(runtime.run(), Outcome::Halted);
+
+        let mut runtime = init_ijvm("files/conformance/no_halt.ijvm");
+        assert_eq!(runtime.run(), Outcome::EndOfProgram);
+        assert_eq!(runtime.program_counter(), 2);
+        assert_eq!(runtime.tos(), 2);
+        // stays at the end
+        assert_eq!(runtime.step(), Outcome::EndOfProgram);
+        assert_eq!(runtime.program_counter(), 2);
+    }
+
+    #[test]
+    fn test_steps() {
+        let mut runtime = init_ijvm("files/conformance/no_halt.ijvm");
+        assert_eq!(runtime.steps(1), Outcome::Running);
+        assert_eq!(runtime.steps(1), Outcome::Running);
+        assert_eq!(runtime.steps(10), Outcome::EndOfProgram);
+        assert!(runtime.is_finished());
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_breakpoint(4);
+
+        assert_eq!(runtime.run(), Outcome::Breakpoint);
+        assert_eq!(runtime.program_counter(), 4);
+        assert_eq!(runtime.tos(), 1);
+
+        // the instruction under the breakpoint executes on resume
+        assert_eq!(runtime.step(), Outcome::Running);
+        assert_eq!(runtime.program_counter(), 5);
+        assert_eq!(runtime.tos(), 2);
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 2);
+
+        let mut runtime = init_ijvm("files/conformance/stack_ops.ijvm");
+        runtime.set_breakpoint(4);
+        runtime.clear_breakpoint(4);
+        assert_eq!(runtime.run(), Outcome::Halted);
+    }
+
+    #[test]
+    fn test_breakpoint_on_waiting_in() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.set_breakpoint(1);
+        runtime.feed_input(b"a");
+
+        assert_eq!(runtime.run(), Outcome::Breakpoint);
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+        // the breakpoint was taken, waiting again doesn't stop on it again
+        assert_eq!(runtime.step(), Outcome::NeedsInput);
+        assert_eq!(runtime.program_counter(), 1);
+        runtime.feed_input(b"b");
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), b'b' as i32);
+
+        // a new run stops on it again
+        runtime.reset();
+        runtime.feed_input(b"ab");
+        assert_eq!(runtime.run(), Outcome::Breakpoint);
+        assert_eq!(runtime.run(), Outcome::Halted);
+    }
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_overflow {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome, OverflowMode, Runtime},
+        trap::TrapKind,
+    };
+
+    fn run_with(file: &str, mode: OverflowMode) -> Runtime {
+        let mut runtime = init_ijvm(file);
+        runtime.set_overflow_mode(mode);
+        runtime.run();
+        runtime
+    }
+
+    fn check_all(file: &str, wrapped: i32, saturated: i32, trap_pc: usize) {
+        let runtime = run_with(file, OverflowMode::Wrapping);
+        assert!(runtime.trap().is_none());
+        assert_eq!(runtime.tos(), wrapped);
+
+        let runtime = run_with(file, OverflowMode::Saturating);
+        assert!(runtime.trap().is_none());
+        assert_eq!(runtime.tos(), saturated);
+
+        let runtime = run_with(file, OverflowMode::Checked);
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::Overflow);
+        assert_eq!(runtime.trap().unwrap().pc, trap_pc);
+    }
+
+    #[test]
+    fn test_iadd_boundary() {
+        check_all("files/overflow/iadd.ijvm", i32::MIN, i32::MAX, 2);
+    }
+
+    #[test]
+    fn test_isub_boundary() {
+        check_all("files/overflow/isub.ijvm", i32::MAX, i32::MIN, 2);
+    }
+
+    #[test]
+    fn test_iinc_boundary() {
+        check_all("files/overflow/iinc.ijvm", i32::MIN, i32::MAX, 2);
+    }
+
+    #[test]
+    fn test_wide_iinc_boundary() {
+        check_all("files/overflow/wide_iinc.ijvm", i32::MIN, i32::MAX, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "ext-arith")]
+    fn test_imul_boundary() {
+        check_all("files/ext/imul_overflow.ijvm", i32::MIN, i32::MAX, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "ext-arith")]
+    fn test_idiv_boundary() {
+        check_all("files/ext/idiv_overflow.ijvm", i32::MIN, i32::MAX, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "ext-arith")]
+    fn test_ineg_boundary() {
+        check_all("files/ext/ineg_overflow.ijvm", i32::MIN, i32::MAX, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "ext-arith")]
+    fn test_irem_never_overflows() {
+        for mode in [
+            OverflowMode::Wrapping,
+            OverflowMode::Checked,
+            OverflowMode::Saturating,
+        ] {
+            let runtime = run_with("files/ext/irem_min.ijvm", mode);
+            assert_eq!(runtime.outcome(), Outcome::Halted);
+            assert_eq!(runtime.tos(), 0);
+        }
+    }
+
+    #[test]
+    fn test_no_overflow_at_boundary() {
+        // IADD and IINC up to MAX, ISUB and IINC down to MIN
+        for mode in [
+            OverflowMode::Wrapping,
+            OverflowMode::Checked,
+            OverflowMode::Saturating,
+        ] {
+            let runtime = run_with("files/overflow/in_range.ijvm", mode);
+            assert_eq!(runtime.outcome(), Outcome::Halted);
+            assert_eq!(
+                runtime.stack_slice(),
+                [i32::MAX, i32::MIN, i32::MAX, i32::MIN]
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_replay {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome},
+        replay::{RecordedInput, Recorder, Recording, ReplayError},
+    };
+
+    fn record(binary: &str, input: &[u8]) -> Recording {
+        let mut runtime = init_ijvm(binary)
+            .with_input(input)
+            .with_output(Vec::new())
+            .with_observer(Recorder::new());
+        runtime.run();
+        runtime.observer().recording().clone()
+    }
+
+    #[test]
+    fn test_record_echo() {
+        let recording = record("files/io/echo.ijvm", b"hi");
+        assert_eq!(recording.output(), b"hi");
+        // every loop takes 5 instructions
+        assert_eq!(
+            recording.input(),
+            [
+                RecordedInput {
+                    at: 0,
+                    byte: Some(b'h')
+                },
+                RecordedInput {
+                    at: 5,
+                    byte: Some(b'i')
+                },
+                RecordedInput { at: 10, byte: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_simplecalc() {
+        let recording = record("files/advanced/SimpleCalc.ijvm", b"99 5 + 4 / 22 1*- ! ?.");
+        assert_eq!(recording.output(), b"24\n");
+
+        let recording = Recording::from_bytes(&recording.to_bytes()).unwrap();
+        let mut runtime = init_ijvm("files/advanced/SimpleCalc.ijvm").with_output(Vec::new());
+        assert_eq!(runtime.replay(&recording), Ok(Outcome::Halted));
+        assert_eq!(runtime.inner.out_stream, b"24\n");
+
+        // and again after a reset
+        runtime.inner.out_stream.clear();
+        runtime.reset();
+        assert_eq!(runtime.replay(&recording), Ok(Outcome::Halted));
+    }
+
+    #[test]
+    fn test_replay_diverges() {
+        let recording = record("files/io/echo.ijvm", b"hi");
+        let mut bytes = recording.to_bytes();
+
+        // the second byte was read after 5 instructions, its count's low byte
+        // comes after the header, the input count and the first input
+        bytes[6 + 8 + 10 + 7] = 4;
+        let moved = Recording::from_bytes(&bytes).unwrap();
+        let mut runtime = init_ijvm("files/io/echo.ijvm").with_output(Vec::new());
+        assert_eq!(
+            runtime.replay(&moved),
+            Err(ReplayError::InputMismatch {
+                recorded: Some(4),
+                read_at: 5
+            })
+        );
+
+        // a different program
+        let mut runtime = init_ijvm("files/io/in2.ijvm").with_output(Vec::new());
+        assert!(runtime.replay(&recording).is_err());
+
+        // the same input, but different output
+        let mut bytes = recording.to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] = b'!';
+        let changed = Recording::from_bytes(&bytes).unwrap();
+        let mut runtime = init_ijvm("files/io/echo.ijvm").with_output(Vec::new());
+        assert_eq!(
+            runtime.replay(&changed),
+            Err(ReplayError::OutputMismatch { offset: 1 })
+        );
+        assert_eq!(runtime.inner.out_stream, b"hi");
+    }
+}
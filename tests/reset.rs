@@ -0,0 +1,57 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_reset {
+    use copp_rs::ijvm_core::{init_ijvm, InputMode, Outcome};
+
+    #[test]
+    fn test_reset_after_interrupt_mid_call() {
+        let fresh = init_ijvm("files/conformance/invoke.ijvm");
+        let mut runtime = init_ijvm("files/conformance/invoke.ijvm");
+
+        // the interrupt is noticed right after INVOKEVIRTUAL, inside f
+        runtime.interrupt_handle().interrupt();
+        assert_eq!(runtime.run(), Outcome::Interrupted);
+        runtime.step();
+        assert_eq!(runtime.snapshot().outcome(), &Outcome::Running);
+        assert_ne!(runtime.snapshot(), fresh.snapshot());
+
+        runtime.reset();
+        assert_eq!(runtime.snapshot(), fresh.snapshot());
+        assert_eq!(runtime.frame().load_var(0), 0);
+        assert_eq!(runtime.tos(), 0);
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 40);
+    }
+
+    #[test]
+    fn test_reset_clears_input_and_locals() {
+        let fresh = init_ijvm("files/conformance/iinc.ijvm");
+        let mut runtime = init_ijvm("files/conformance/iinc.ijvm");
+        assert_eq!(runtime.run(), Outcome::Halted);
+        runtime.reset();
+        assert_eq!(runtime.snapshot(), fresh.snapshot());
+
+        let fresh = init_ijvm("files/io/in2.ijvm");
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.feed_input(b"abc");
+        runtime.close_input();
+        runtime.step();
+        runtime.reset();
+        assert_eq!(runtime.snapshot(), fresh.snapshot());
+
+        // nothing fed is left over, so IN waits again
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+    }
+
+    #[test]
+    fn test_reset_drops_pending_interrupt() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.interrupt_handle().interrupt();
+        runtime.reset();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 55);
+    }
+}
@@ -0,0 +1,222 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_snapshot {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, InputMode, Outcome},
+        snapshot::{Snapshot, SnapshotError},
+    };
+
+    #[test]
+    fn test_restore_inside_method() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+        let bytes = runtime.snapshot().to_bytes();
+        assert_eq!(runtime.run(), Outcome::Halted);
+
+        let mut restored = init_ijvm("files/conformance/recursion.ijvm");
+        restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(restored.snapshot().to_bytes(), bytes);
+        assert_eq!(restored.run(), Outcome::Halted);
+        assert_eq!(restored.snapshot(), runtime.snapshot());
+        assert_eq!(restored.tos(), 55);
+    }
+
+    #[test]
+    fn test_restore_forks() {
+        let mut runtime = init_ijvm("files/conformance/invoke.ijvm");
+        runtime.steps(6);
+        let snapshot = runtime.snapshot();
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 40);
+
+        // going back in the same runtime
+        runtime.restore(&snapshot).unwrap();
+        assert!(!runtime.is_finished());
+        assert_eq!(runtime.frame().load_var(1), 5);
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.tos(), 40);
+    }
+
+    #[test]
+    fn test_finished_state_and_input_position() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.feed_input(b"a");
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+
+        let snapshot = Snapshot::from_bytes(&runtime.snapshot().to_bytes()).unwrap();
+        assert_eq!(snapshot.input_position(), 1);
+        assert_eq!(snapshot.outcome(), &Outcome::NeedsInput);
+
+        let mut restored = init_ijvm("files/io/in2.ijvm");
+        restored.set_input_mode(InputMode::NonBlocking);
+        restored.restore(&snapshot).unwrap();
+        assert!(restored.is_finished());
+        restored.feed_input(b"b");
+        assert_eq!(restored.run(), Outcome::Halted);
+        assert_eq!(restored.tos(), b'b' as i32);
+        assert_eq!(restored.snapshot().input_position(), 2);
+    }
+
+    #[test]
+    fn test_restore_waiting_under_breakpoint() {
+        let mut runtime = init_ijvm("files/io/in2.ijvm");
+        runtime.set_input_mode(InputMode::NonBlocking);
+        runtime.set_breakpoint(1);
+        runtime.feed_input(b"a");
+        assert_eq!(runtime.run(), Outcome::Breakpoint);
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+        let bytes = runtime.snapshot().to_bytes();
+
+        let mut restored = init_ijvm("files/io/in2.ijvm");
+        restored.set_input_mode(InputMode::NonBlocking);
+        restored.set_breakpoint(1);
+        restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap())
+            .unwrap();
+        // the breakpoint was already taken, in both
+        assert_eq!(runtime.run(), Outcome::NeedsInput);
+        assert_eq!(restored.run(), Outcome::NeedsInput);
+        restored.feed_input(b"b");
+        assert_eq!(restored.run(), Outcome::Halted);
+        assert_eq!(restored.tos(), b'b' as i32);
+    }
+
+    #[test]
+    fn test_program_mismatch() {
+        let mut runtime = init_ijvm("files/conformance/invoke.ijvm");
+        let snapshot = init_ijvm("files/conformance/recursion.ijvm").snapshot();
+        assert_eq!(
+            runtime.restore(&snapshot),
+            Err(SnapshotError::ProgramMismatch)
+        );
+    }
+
+    #[test]
+    fn test_bad_bytes() {
+        let bytes = init_ijvm("files/conformance/invoke.ijvm")
+            .snapshot()
+            .to_bytes();
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated)
+        );
+        assert_eq!(Snapshot::from_bytes(b"nope"), Err(SnapshotError::BadMagic));
+
+        let mut newer = bytes.clone();
+        newer[5] = 5;
+        assert_eq!(
+            Snapshot::from_bytes(&newer),
+            Err(SnapshotError::UnsupportedVersion(5))
+        );
+    }
+
+    #[test]
+    fn test_nested_no_return_is_corrupt() {
+        let bytes = init_ijvm("files/conformance/invoke.ijvm")
+            .snapshot()
+            .to_bytes();
+        // the outcome follows the magic, version, program and pc; wrap it in
+        // NoReturn traps, each a Trapped outcome of trap kind 12
+        let (head, tail) = bytes.split_at(22);
+        let mut nested = head.to_vec();
+        for _ in 0..1_000_000 {
+            nested.extend_from_slice(&[3, 12]);
+        }
+        nested.extend_from_slice(tail);
+        assert_eq!(
+            Snapshot::from_bytes(&nested),
+            Err(SnapshotError::Corrupt("nested trap"))
+        );
+    }
+
+    #[test]
+    fn test_restore_checks_call_depth() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+        let snapshot = runtime.snapshot();
+        let depth = runtime.call_stack().count() - 1;
+        assert!(depth > 1);
+
+        let mut shallow = init_ijvm("files/conformance/recursion.ijvm");
+        shallow.set_max_call_depth(depth - 1);
+        assert_eq!(
+            shallow.restore(&snapshot),
+            Err(SnapshotError::Corrupt("call depth"))
+        );
+        shallow.set_max_call_depth(depth);
+        assert_eq!(shallow.restore(&snapshot), Ok(()));
+    }
+
+    /// Where each frame starts in an encoded snapshot of a running machine, main first.
+    fn frame_offsets(bytes: &[u8]) -> Vec<usize> {
+        let u32_at = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        // magic, version, program, pc, a Running outcome and the top value
+        assert_eq!(bytes[22], 0);
+        let mut at = 27;
+        at += 4 + 4 * u32_at(at);
+        let count = u32_at(at);
+        at += 4;
+        (0..count)
+            .map(|_| {
+                let frame = at;
+                // stack base, return address and method, then the locals
+                at += 20;
+                at += 4 + 4 * u32_at(at);
+                frame
+            })
+            .collect()
+    }
+
+    /// `bytes` with the locals of the frame at `frame` cut down to `count`.
+    fn with_locals(bytes: &[u8], frame: usize, count: u32) -> Snapshot {
+        let at = frame + 20;
+        let len = u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        let mut tampered = bytes[..at].to_vec();
+        tampered.extend_from_slice(&count.to_be_bytes());
+        tampered.extend_from_slice(&bytes[at + 4..at + 4 + 4 * count as usize]);
+        tampered.extend_from_slice(&bytes[at + 4 + 4 * len..]);
+        Snapshot::from_bytes(&tampered).unwrap()
+    }
+
+    #[test]
+    fn test_restore_checks_frames() {
+        let mut runtime = init_ijvm("files/conformance/recursion.ijvm");
+        runtime.steps(40);
+        let bytes = runtime.snapshot().to_bytes();
+        let frames = frame_offsets(&bytes);
+        let innermost = *frames.last().unwrap();
+        assert_eq!(
+            runtime.restore(&with_locals(&bytes, innermost, 1)),
+            Err(SnapshotError::Corrupt("frame locals"))
+        );
+
+        // a method that isn't one
+        let mut tampered = bytes.clone();
+        tampered[innermost + 12..innermost + 20].copy_from_slice(&0u64.to_be_bytes());
+        assert_eq!(
+            runtime.restore(&Snapshot::from_bytes(&tampered).unwrap()),
+            Err(SnapshotError::Corrupt("frame method"))
+        );
+
+        // a base above the top of the stack
+        let mut tampered = bytes.clone();
+        tampered[innermost..innermost + 4].copy_from_slice(&1000u32.to_be_bytes());
+        assert_eq!(
+            runtime.restore(&Snapshot::from_bytes(&tampered).unwrap()),
+            Err(SnapshotError::Corrupt("frame stack base"))
+        );
+
+        // main's locals, which ILOAD would read past
+        let mut runtime = init_ijvm("files/conformance/iinc.ijvm");
+        let bytes = runtime.snapshot().to_bytes();
+        assert_eq!(
+            runtime.restore(&with_locals(&bytes, frame_offsets(&bytes)[0], 0)),
+            Err(SnapshotError::Corrupt("frame locals"))
+        );
+        assert_eq!(runtime.run(), Outcome::Halted);
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 #[cfg(test)]
 mod tests_5 {
     use copp_rs::ijvm_core::init_ijvm;
@@ -0,0 +1,151 @@
+#![cfg(all(feature = "std", feature = "green-threads"))]
+
+#[cfg(test)]
+mod tests_threads {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, InstructionRef, Outcome},
+        observer::Observer,
+        snapshot::Snapshot,
+        threads::ThreadId,
+        trap::TrapKind,
+    };
+
+    #[derive(Default)]
+    struct Switches {
+        spawned: Vec<(ThreadId, InstructionRef)>,
+        switches: Vec<(ThreadId, ThreadId)>,
+    }
+
+    impl Observer for Switches {
+        fn on_spawn(&mut self, thread: ThreadId, method: InstructionRef) {
+            self.spawned.push((thread, method));
+        }
+        fn on_thread_switch(&mut self, from: ThreadId, to: ThreadId) {
+            self.switches.push((from, to));
+        }
+    }
+
+    #[test]
+    fn test_yield_and_join() {
+        let mut runtime = init_ijvm("files/threads/pingpong.ijvm")
+            .with_output(Vec::new())
+            .with_observer(Switches::default());
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream, b"ababab");
+        assert_eq!(runtime.tos(), 97 + 98);
+        assert_eq!(runtime.thread_id(), 0);
+
+        let observer = runtime.observer();
+        assert_eq!(observer.spawned.len(), 2);
+        assert_eq!(observer.spawned[0].0, 1);
+        assert_eq!(observer.spawned[1].0, 2);
+        // main waits, then the players take turns until both are done
+        assert_eq!(
+            observer.switches,
+            vec![
+                (0, 1),
+                (1, 2),
+                (2, 1),
+                (1, 2),
+                (2, 1),
+                (1, 2),
+                (2, 1),
+                (1, 2),
+                (2, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_slice() {
+        let mut runtime = init_ijvm("files/threads/busy.ijvm").with_output(Vec::new());
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream, b"aaaabbbb");
+
+        // a spinner's loop takes 6 instructions
+        let mut runtime = init_ijvm("files/threads/busy.ijvm").with_output(Vec::new());
+        runtime.set_time_slice(6);
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream, b"abababab");
+        assert_eq!(runtime.tos(), 0);
+    }
+
+    #[test]
+    fn test_scheduling_is_deterministic() {
+        let run = |slice| {
+            let mut runtime = init_ijvm("files/threads/busy.ijvm").with_output(Vec::new());
+            runtime.set_time_slice(slice);
+            runtime.run();
+            runtime.inner.out_stream
+        };
+        for slice in 1..20 {
+            let output = run(slice);
+            assert_eq!(output, run(slice));
+            assert_eq!(output.iter().filter(|c| **c == b'a').count(), 4);
+            assert_eq!(output.iter().filter(|c| **c == b'b').count(), 4);
+        }
+    }
+
+    #[test]
+    fn test_join_failures() {
+        let mut runtime = init_ijvm("files/threads/deadlock.ijvm");
+        runtime.run();
+        let trap = runtime.trap().unwrap();
+        assert_eq!(trap.kind, TrapKind::Deadlock);
+        // the waiter's JOIN
+        assert_eq!(runtime.thread_id(), 1);
+        assert_eq!(trap.call_depth, 1);
+
+        let mut runtime = init_ijvm("files/threads/badjoin.ijvm");
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::BadThread);
+    }
+
+    #[test]
+    fn test_reset_ends_threads() {
+        let mut runtime = init_ijvm("files/threads/pingpong.ijvm").with_output(Vec::new());
+        while runtime.thread_id() == 0 {
+            runtime.step();
+        }
+        runtime.reset();
+        assert_eq!(runtime.thread_id(), 0);
+        assert_eq!(runtime.program_counter(), 0);
+
+        assert_eq!(runtime.run(), Outcome::Halted);
+        assert_eq!(runtime.inner.out_stream, b"ababab");
+    }
+
+    #[test]
+    fn test_snapshot_every_thread() {
+        for (file, slice) in [("pingpong", 1000), ("busy", 6)] {
+            let path = format!("files/threads/{file}.ijvm");
+            let mut runtime = init_ijvm(&path).with_output(Vec::new());
+            runtime.set_time_slice(slice);
+            // main waits in JOIN, and the other player isn't running
+            while runtime.thread_id() != 2 {
+                runtime.step();
+            }
+            runtime.steps(3);
+            let bytes = runtime.snapshot().to_bytes();
+            let written = runtime.inner.out_stream.len();
+            assert_eq!(runtime.run(), Outcome::Halted);
+
+            let mut restored = init_ijvm(&path).with_output(Vec::new());
+            restored.set_time_slice(slice);
+            restored
+                .restore(&Snapshot::from_bytes(&bytes).unwrap())
+                .unwrap();
+            assert_eq!(restored.thread_id(), 2);
+            assert_eq!(restored.snapshot().to_bytes(), bytes);
+            assert_eq!(restored.run(), Outcome::Halted);
+            assert_eq!(restored.inner.out_stream, runtime.inner.out_stream[written..]);
+            assert_eq!(restored.tos(), runtime.tos());
+
+            // and back into the finished runtime
+            runtime.restore(&Snapshot::from_bytes(&bytes).unwrap()).unwrap();
+            runtime.inner.out_stream.clear();
+            assert_eq!(runtime.run(), Outcome::Halted);
+            assert_eq!(runtime.inner.out_stream, restored.inner.out_stream);
+        }
+    }
+}
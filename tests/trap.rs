@@ -0,0 +1,142 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests_trap {
+    use copp_rs::{
+        ijvm_core::{init_ijvm, Outcome, Program},
+        tiny::StackSize,
+        trap::{Trap, TrapKind},
+    };
+
+    #[test]
+    fn test_err_traps() {
+        let mut runtime = init_ijvm("files/traps/err.ijvm");
+
+        let outcome = runtime.run();
+        assert_eq!(
+            outcome,
+            Outcome::Trapped(Trap {
+                kind: TrapKind::Err,
+                pc: 1,
+                call_depth: 0,
+            })
+        );
+        // the runtime is still inspectable, and stays stopped
+        assert_eq!(runtime.tos(), 1);
+        assert_eq!(runtime.run(), outcome);
+    }
+
+    #[test]
+    fn test_ireturn_in_main_traps() {
+        let mut runtime = init_ijvm("files/traps/ireturn_main.ijvm");
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::ReturnFromMain);
+        assert_eq!(runtime.trap().unwrap().pc, 1);
+    }
+
+    #[test]
+    fn test_stack_underflow_traps() {
+        let mut runtime = init_ijvm("files/traps/underflow.ijvm");
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackUnderflow);
+        assert_eq!(runtime.trap().unwrap().pc, 2);
+    }
+
+    #[test]
+    fn test_trapping_instruction_does_nothing() {
+        let mut runtime = init_ijvm("files/traps/underflow_iadd.ijvm");
+
+        runtime.steps(2);
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackUnderflow);
+        assert_eq!(runtime.trap().unwrap().pc, 1);
+        // IADD neither popped its one operand nor pushed a result, and the pc is on it
+        assert_eq!(runtime.stack_slice(), [7]);
+        assert_eq!(runtime.program_counter(), 1);
+        // and stays there
+        runtime.step();
+        assert_eq!(runtime.program_counter(), 1);
+    }
+
+    #[test]
+    fn test_invoke_underflow_stays_at_call() {
+        let mut runtime = init_ijvm("files/traps/invoke_underflow.ijvm");
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackUnderflow);
+        assert_eq!(runtime.trap().unwrap().call_depth, 0);
+        // still on the call, the method body never started
+        assert_eq!(runtime.program_counter(), 1);
+        assert_eq!(runtime.tos(), 1);
+    }
+
+    #[test]
+    fn test_stack_overflow_traps() {
+        let mut program = Program::load("files/traps/overflow.ijvm");
+        program.set_stack_size(StackSize::fixed(100));
+        let mut runtime = program.runtime();
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackOverflow);
+        assert_eq!(runtime.trap().unwrap().pc, 0);
+        assert_eq!(runtime.inner.visit_stack().len(), 100);
+    }
+
+    #[test]
+    fn test_stack_grows_to_max() {
+        let mut program = Program::load("files/traps/overflow.ijvm");
+        program.set_stack_size(StackSize::growable(4, 5000));
+        let mut runtime = program.runtime();
+        assert_eq!(runtime.inner.visit_stack().stack.len(), 5);
+
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackOverflow);
+        assert_eq!(runtime.inner.visit_stack().len(), 5000);
+        assert_eq!(runtime.tos(), 1);
+
+        // a maximum below the smallest growth step
+        let mut program = Program::load("files/traps/overflow.ijvm");
+        program.set_stack_size(StackSize::growable(2, 8));
+        let mut runtime = program.runtime();
+        runtime.run();
+        assert_eq!(runtime.trap().unwrap().kind, TrapKind::StackOverflow);
+        assert_eq!(runtime.inner.visit_stack().len(), 8);
+
+        // the default is as big as the old fixed stack
+        let mut runtime = init_ijvm("files/traps/overflow.ijvm");
+        runtime.run();
+        assert_eq!(runtime.inner.visit_stack().len(), 1 << 16);
+    }
+
+    #[test]
+    fn test_call_depth_exceeded() {
+        let mut runtime = init_ijvm("files/traps/recurse.ijvm");
+        runtime.set_max_call_depth(100);
+
+        runtime.run();
+        let trap = runtime.trap().unwrap();
+        assert_eq!(trap.pc, 5);
+        assert_eq!(trap.call_depth, 100);
+        assert_eq!(
+            trap.kind,
+            TrapKind::CallDepthExceeded {
+                backtrace: vec![5; 16]
+            }
+        );
+        // the call never entered f again
+        assert_eq!(runtime.program_counter(), 5);
+        assert_eq!(runtime.inner.frames().depth(), 100);
+
+        // a shallow limit shows main's call too
+        let mut runtime = init_ijvm("files/traps/recurse.ijvm");
+        runtime.set_max_call_depth(3);
+        runtime.run();
+        assert_eq!(
+            runtime.trap().unwrap().kind,
+            TrapKind::CallDepthExceeded {
+                backtrace: vec![5, 5, 1]
+            }
+        );
+    }
+}